@@ -1,11 +1,166 @@
-use clap::{ArgAction, Parser, ValueEnum};
+use chrono::{Local, NaiveDate, TimeZone};
+use clap::{Parser, Subcommand, ValueEnum};
+use nostr_sdk::Timestamp;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+#[derive(Clone, PartialEq, Eq, Debug, Subcommand)]
 pub enum Command {
-    Post,
-    Fetch,
-    Stream,
-    Contacts
+    /// Publish a note from an argument, stdin or $EDITOR
+    Post {
+        /// Note content, `-` reads stdin. When omitted, piped stdin or $EDITOR is used
+        content: Option<String>,
+        /// note, nevent or hex id of the event to reply to
+        #[arg(long, value_name = "EVENT")]
+        reply_to: Option<String>,
+        /// note, nevent or hex id of an event to quote, referenced at the end of the note
+        #[arg(long, value_name = "EVENT")]
+        quote: Option<String>,
+        /// Print the signed event JSON instead of publishing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Repost a note to your followers
+    Repost {
+        /// note, nevent or hex id of the event to repost
+        event: String,
+        /// Print the signed event JSON instead of publishing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Fetch notes from contacts into the cache and print them
+    Fetch {
+        /// Start of the window: unix time, YYYY-MM-DD or an age like 90m, 12h, 7d. Defaults to the last login
+        #[arg(long, value_parser = parse_time)]
+        since: Option<Timestamp>,
+        /// End of the window, same formats as --since
+        #[arg(long, value_parser = parse_time)]
+        until: Option<Timestamp>,
+        /// Only fetch notes by this npub or hex key, can be repeated
+        #[arg(long = "author", value_name = "NPUB")]
+        authors: Vec<String>,
+        /// Maximum number of notes to return
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Output format for the fetched notes
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+        /// Don't write to the cache or update the last login
+        #[arg(long)]
+        no_cache: bool,
+    },
+    /// Print new notes from contacts as they arrive
+    Stream {
+        /// Output format, one note per line
+        #[arg(long, value_enum, default_value_t = StreamFormat::Text)]
+        format: StreamFormat,
+    },
+    /// List, follow and unfollow contacts
+    Contacts {
+        #[command(subcommand)]
+        action: Option<ContactsCommand>,
+    },
+    /// Show connection state, latency and messages for each relay
+    Relays,
+    /// List, resume, publish and delete unfinished notes
+    Drafts {
+        #[command(subcommand)]
+        action: Option<DraftsCommand>,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Subcommand)]
+pub enum DraftsCommand {
+    /// List drafts, most recently edited first
+    List,
+    /// Continue a draft in $EDITOR and publish it
+    Resume {
+        /// `new` or the note, nevent or hex id of the event the draft replies to
+        draft: String,
+    },
+    /// Publish a draft as it is
+    Publish {
+        /// `new` or the note, nevent or hex id of the event the draft replies to
+        draft: String,
+    },
+    /// Delete a draft without publishing it
+    Delete {
+        /// `new` or the note, nevent or hex id of the event the draft replies to
+        draft: String,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Subcommand)]
+pub enum ContactsCommand {
+    /// List followed contacts with their npub and where they come from
+    List,
+    /// Follow a public key and publish the updated contact list
+    Add {
+        /// npub, hex or nostr: uri of the key to follow
+        pubkey: String,
+        /// Petname to publish and show in the feed
+        #[arg(long)]
+        name: Option<String>,
+        /// Relay hint for the followed key
+        #[arg(long)]
+        relay: Option<String>,
+        /// Start a new contact list from the config when none is found on relays
+        #[arg(long)]
+        init: bool,
+    },
+    /// Unfollow a public key and publish the updated contact list
+    Remove {
+        /// npub, hex or nostr: uri of the key to unfollow
+        pubkey: String,
+        /// Start a new contact list from the config when none is found on relays
+        #[arg(long)]
+        init: bool,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum StreamFormat {
+    /// `[datetime] user: content`
+    Text,
+    /// One JSON encoded post per line
+    Json,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns with the first line of each note
+    Table,
+    /// A single JSON array of posts
+    Json,
+    /// One JSON encoded post per line
+    Jsonl,
+}
+
+/// Parse a point in time given as unix seconds, a `YYYY-MM-DD` local date or an age such as `12h`
+pub fn parse_time(value: &str) -> Result<Timestamp, String> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Timestamp::from_secs(secs));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+        return Local.from_local_datetime(&midnight)
+            .earliest()
+            .map(|datetime| Timestamp::from_secs(datetime.timestamp().max(0) as u64))
+            .ok_or_else(|| format!("{} doesn't exist in the local timezone", value));
+    }
+
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse()
+        .map_err(|_| format!("expected unix time, YYYY-MM-DD or an age like 12h, got {}", value))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return Err(format!("unknown time unit {:?}, expected one of s, m, h, d, w", unit)),
+    };
+    Ok(Timestamp::now() - Timestamp::from_secs(amount * unit_secs))
 }
 
 #[allow(
@@ -18,8 +173,7 @@ pub enum Command {
     disable_help_flag = true
 )]
 pub struct Flags {
-    /// post to nostr
-    #[arg(default_value = None)]
+    #[command(subcommand)]
     pub command: Option<Command>,
 }
 
@@ -33,21 +187,36 @@ impl Flags {
 
     /// Check if the command is "post"
     pub fn post(&self) -> bool {
-        matches!(self.command, Some(Command::Post))
+        matches!(self.command, Some(Command::Post { .. }))
+    }
+
+    /// Check if the command is "repost"
+    pub fn repost(&self) -> bool {
+        matches!(self.command, Some(Command::Repost { .. }))
     }
 
     /// Check if the command is "fetch"
     pub fn fetch(&self) -> bool {
-        matches!(self.command, Some(Command::Fetch))
+        matches!(self.command, Some(Command::Fetch { .. }))
     }
 
     /// Check if the command is "stream"
     pub fn stream(&self) -> bool {
-        matches!(self.command, Some(Command::Stream))
+        matches!(self.command, Some(Command::Stream { .. }))
     }
 
     /// Check if the command is "contacts"
     pub fn contacts(&self) -> bool {
-        matches!(self.command, Some(Command::Contacts))
+        matches!(self.command, Some(Command::Contacts { .. }))
+    }
+
+    /// Check if the command is "relays"
+    pub fn relays(&self) -> bool {
+        matches!(self.command, Some(Command::Relays))
+    }
+
+    /// Check if the command is "drafts"
+    pub fn drafts(&self) -> bool {
+        matches!(self.command, Some(Command::Drafts { .. }))
     }
 }
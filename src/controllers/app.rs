@@ -1,17 +1,36 @@
 use std::env;
 use std::fs;
 use std::process::Command;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use ratatui::Terminal;
-use nostr_sdk::Timestamp;
+use nostr_sdk::{EventId, Kind, PublicKey, RelayPoolNotification, Timestamp};
+use tokio::sync::broadcast::error::RecvError;
 use std::io;
 
-use crate::models::{NostrClient, Config, Post};
+use crate::models::{NostrClient, Config, FeedQuery, Notification, NotificationStore, Post, ProfileStore, PublishResult, ReactionStore, RelayFetchSummary, ReplyTo, SyncState};
+use crate::models::sync::GAP_THRESHOLD_SECS;
 use crate::views::{tui, StatefulList};
-use crate::models::cache;
+use crate::models::{cache, draft, nip18, outbox, pow};
+use crate::models::pow::MiningJob;
+use crate::controllers::post::describe_mining;
 use crate::error::NostratuiError;
 
+const OUTBOX_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+/// Notes requested per page of older history
+const PAGE_SIZE: usize = 50;
+/// How close to the bottom the selection gets before the next page is requested
+const PAGE_TRIGGER_DISTANCE: usize = 5;
+/// Wait before asking relays for the same page again after a failure
+const PAGE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+/// Posts either side of the selection whose reactions are kept up to date
+const REACTION_WINDOW: usize = 10;
+/// How long reaction counts are shown before being fetched again
+const REACTION_REFRESH: std::time::Duration = std::time::Duration::from_secs(120);
+/// How often the relay panel asks the pool for fresh state while it's open
+const RELAY_REFRESH: std::time::Duration = std::time::Duration::from_secs(1);
+
 pub async fn init_feed(client: &mut NostrClient, config: &mut crate::models::Config, fetch_time: Timestamp) -> Result<(),NostratuiError> {
     // Get contacts
     let conf_contacts = config.contacts.clone();
@@ -24,8 +43,12 @@ pub async fn init_feed(client: &mut NostrClient, config: &mut crate::models::Con
     }
 
     // Get posts to read, add to cache
-    let new_posts = client.fetch_notes_since(fetch_time).await?;
-    crate::models::cache::save_posts_to_cache(new_posts)?;
+    let mut sync = SyncState::load()?;
+    log_sync_gaps(client, &sync, fetch_time);
+    let fetched = client.sync_notes(&mut sync, fetch_time).await?;
+    log_relay_summaries(&fetched.relays);
+    crate::models::cache::save_events_to_cache(fetched.events)?;
+    sync.save()?;
 
     // Save new config
     config.update_last_login();
@@ -39,7 +62,8 @@ pub async fn start_app(client: NostrClient, config: Config) -> std::result::Resu
 
     // Get new posts
     let mut posts = cache::load_cached_posts()
-        .map_err(|e| Box::<dyn std::error::Error>::from(e))?;
+        .map_err(Box::<dyn std::error::Error>::from)?;
+    posts.retain(|post| pow::difficulty_of(&post.id) >= config.min_incoming_pow);
     posts.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
     
     // Create our stateful list
@@ -67,41 +91,545 @@ pub async fn run_app(
     config: Config,
 ) -> Result<(),NostratuiError> {
     let mut refresh_in_progress = false;
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<Post>>(1);
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<nostr_sdk::Event>>(1);
     let mut thread_view: Option<tui::ThreadView> = None;
 
+    // Threads load in the background, updates from a replaced or cancelled load are dropped by id
+    let (thread_tx, mut thread_rx) = tokio::sync::mpsc::unbounded_channel::<ThreadUpdate>();
+    let mut thread_task: Option<tokio::task::JoinHandle<()>> = None;
+    let mut thread_load_id: u64 = 0;
+    let mut outbox_view: Option<tui::OutboxView> = None;
+
+    // Errors and events from here and from background tasks end up in the status bar and log
+    let (ui_tx, mut ui_rx) = tokio::sync::mpsc::unbounded_channel::<tui::UiMessage>();
+    let mut messages = tui::Messages::default();
+    let mut message_log: Option<tui::MessageLogView> = None;
+    let mut relay_view: Option<tui::RelayView> = None;
+    let mut relay_refreshed = std::time::Instant::now();
+    let mut composer: Option<tui::Composer> = None;
+    let mut drafts_view: Option<tui::DraftsView> = None;
+
+    // Posts cached before events were kept are swapped for their signed originals in the background
+    spawn_legacy_upgrade(Arc::clone(&client), ui_tx.clone());
+
+    // Outbox changes are announced here so the unsent count and view stay current
+    let (outbox_tx, mut outbox_rx) = tokio::sync::mpsc::channel::<()>(8);
+    let mut outbox_entries = outbox::load_outbox().unwrap_or_else(|e| {
+        messages.push(tui::UiMessage::Error(String::from("Error loading outbox"), e));
+        Vec::new()
+    });
+    spawn_outbox_retries(Arc::clone(&client), outbox_tx.clone(), ui_tx.clone());
+
+    // Names are resolved while rendering, metadata for unnamed authors arrives here
+    let mut profiles = ProfileStore::load(&config.contacts).unwrap_or_else(|e| {
+        messages.push(tui::UiMessage::Error(String::from("Error loading profiles"), e));
+        ProfileStore::with_contacts(&config.contacts)
+    });
+    let profile_ttl = config.profile_ttl;
+    let (profile_tx, mut profile_rx) = tokio::sync::mpsc::channel::<ProfileStore>(8);
+    let contact_keys = client.contact_keys();
+    let mut stale = stale_authors(&stateful_list.items, &profiles, profile_ttl);
+    stale.extend(profiles.stale(&contact_keys, profile_ttl));
+    spawn_profile_fetch(Arc::clone(&client), stale, profile_tx.clone(), ui_tx.clone());
+
+    // Reposts that didn't embed the note they repost get it fetched separately
+    let (repost_tx, mut repost_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<nostr_sdk::Event>>();
+    spawn_repost_fetch(Arc::clone(&client), &stateful_list.items, repost_tx.clone(), ui_tx.clone());
+
+    // New notes from contacts are pushed as they arrive instead of waiting for Ctrl-r
+    let (live_tx, mut live_rx) = tokio::sync::mpsc::channel::<Vec<nostr_sdk::Event>>(64);
+    spawn_live_feed(Arc::clone(&client), live_tx, ui_tx.clone());
+    let mut known_ids: HashSet<String> = stateful_list.items.iter().map(|post| post.id.clone()).collect();
+    let mut new_above = 0;
+
+    // Older pages are requested as the selection nears the bottom of the feed
+    let (page_tx, mut page_rx) = tokio::sync::mpsc::channel::<Result<Vec<nostr_sdk::Event>, NostratuiError>>(1);
+    let mut page_loading = false;
+    let mut history_exhausted = false;
+    let mut page_retry_at: Option<std::time::Instant> = None;
+    let mut oldest_fetched: Option<u64> = None;
+    // Second the page in flight reaches back from
+    let mut page_boundary: u64 = 0;
+
+    // Notes below this much proof of work are kept out of the feed
+    let min_pow = config.min_incoming_pow;
+
+    // Notes being mined before they're sent, the first one's progress is shown
+    let mut mining_jobs: Vec<Arc<MiningJob>> = Vec::new();
+
+    // Reactions to the posts on screen are fetched in the background and refreshed now and then
+    let (reaction_tx, mut reaction_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<nostr_sdk::Event>>();
+    let mut reactions = ReactionStore::default();
+    let mut reactions_requested: HashMap<String, std::time::Instant> = HashMap::new();
+    let mut reaction_picker: Option<tui::ReactionPicker> = None;
+
+    // Replies, mentions, reactions, reposts and zaps tagging us, from anyone, kept with their read state
+    let mut notifications = NotificationStore::load().unwrap_or_else(|e| {
+        messages.push(tui::UiMessage::Error(String::from("Error loading notifications"), e));
+        NotificationStore::default()
+    });
+    let mut notifications_view: Option<tui::NotificationsView> = None;
+    let (notify_tx, mut notify_rx) = tokio::sync::mpsc::channel::<Vec<nostr_sdk::Event>>(64);
+    spawn_notifications(Arc::clone(&client), notifications.since(), notify_tx, ui_tx.clone());
+
     loop {
-        let status_message = if refresh_in_progress {
+        let mut status_message = if refresh_in_progress {
             String::from("Refreshing...")
         } else {
             String::from("Feed")
         };
+        if new_above > 0 {
+            status_message.push_str(&format!(" | {} new posts (t)", new_above));
+        }
+        if !outbox_entries.is_empty() {
+            status_message.push_str(&format!(" | {} unsent (o)", outbox_entries.len()));
+        }
+        let unread = notifications.unread(None);
+        if unread > 0 {
+            status_message.push_str(&format!(" | {} unread (N)", unread));
+        }
+        mining_jobs.retain(|job| !job.is_finished());
+        if let Some(job) = mining_jobs.first().filter(|job| job.difficulty > 0) {
+            status_message.push_str(&format!(" | {} (x to cancel)", describe_mining(job)));
+        }
+        if page_loading {
+            status_message.push_str(" | Loading older posts...");
+        } else if history_exhausted {
+            status_message.push_str(" | No older posts on relays");
+        }
 
-        if let Some(thread_view) = &thread_view {
-            terminal.draw(|f| tui::render_thread_view(f, thread_view))?;
-        } else {
-            terminal.draw(|f| tui::render_ui(f, stateful_list, status_message))?;
+        while let Ok(message) = ui_rx.try_recv() {
+            messages.push(message);
+        }
+
+        if let Some(relay_view) = relay_view.as_mut().filter(|_| relay_refreshed.elapsed() >= RELAY_REFRESH) {
+            relay_view.set_relays(client.relay_health().await);
+            relay_refreshed = std::time::Instant::now();
+        }
+
+        terminal.draw(|f| {
+            let (area, status_area) = tui::split_status_bar(f.size());
+            if let Some(message_log) = &mut message_log {
+                tui::render_message_log(f, area, &messages, message_log);
+            } else if let Some(relay_view) = &mut relay_view {
+                tui::render_relay_view(f, area, relay_view);
+            } else if let Some(outbox_view) = &mut outbox_view {
+                tui::render_outbox_view(f, area, outbox_view);
+            } else if let Some(notifications_view) = &mut notifications_view {
+                tui::render_notifications_view(f, area, notifications_view, &notifications, &profiles);
+            } else if let Some(drafts_view) = &mut drafts_view {
+                tui::render_drafts_view(f, area, drafts_view, &profiles);
+            } else if let Some(composer) = &composer {
+                tui::render_composer(f, area, composer, &profiles);
+            } else if let Some(thread_view) = &thread_view {
+                tui::render_thread_view(f, area, thread_view, &profiles, &reactions);
+            } else {
+                tui::render_ui(f, area, stateful_list, &profiles, &reactions);
+            }
+            if let Some(picker) = &reaction_picker {
+                tui::render_reaction_picker(f, area, picker, &profiles);
+            }
+            tui::render_status_bar(f, status_area, &status_message, &messages);
+        })?;
+
+        while let Ok(update) = thread_rx.try_recv() {
+            let Some(thread_view) = thread_view.as_mut().filter(|_| update.load_id() == thread_load_id) else {
+                continue;
+            };
+            match update {
+                ThreadUpdate::Posts(_, posts) => {
+                    spawn_profile_fetch(Arc::clone(&client), stale_authors(&posts, &profiles, profile_ttl), profile_tx.clone(), ui_tx.clone());
+                    thread_view.add_posts(posts);
+                },
+                ThreadUpdate::Finished(_, result) => {
+                    thread_view.loading = false;
+                    thread_view.error = result.as_ref().err().map(|e| format!("Failed to load thread: {}", e));
+                    if let Err(e) = result {
+                        messages.push(tui::UiMessage::Error(String::from("Error loading thread"), e));
+                    }
+                    thread_task = None;
+                },
+            }
         }
 
-        if let Ok(new_posts) = rx.try_recv() {
-            cache::save_posts_to_cache(new_posts.clone())?;
-            stateful_list.add_items(new_posts);
-            stateful_list.items.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
+        if let Ok(new_events) = rx.try_recv() {
+            let (new_posts, _) = add_new_events(stateful_list, &mut known_ids, new_events, min_pow, &mut messages);
+            spawn_profile_fetch(Arc::clone(&client), stale_authors(&new_posts, &profiles, profile_ttl), profile_tx.clone(), ui_tx.clone());
+            spawn_repost_fetch(Arc::clone(&client), &new_posts, repost_tx.clone(), ui_tx.clone());
             refresh_in_progress = false;
         }
 
+        while let Ok(new_events) = live_rx.try_recv() {
+            let (new_posts, inserted_above) = add_new_events(stateful_list, &mut known_ids, new_events, min_pow, &mut messages);
+            spawn_profile_fetch(Arc::clone(&client), stale_authors(&new_posts, &profiles, profile_ttl), profile_tx.clone(), ui_tx.clone());
+            spawn_repost_fetch(Arc::clone(&client), &new_posts, repost_tx.clone(), ui_tx.clone());
+            new_above += inserted_above;
+        }
+        if stateful_list.state.selected() == Some(0) {
+            new_above = 0;
+        }
+
+        if let Ok(page) = page_rx.try_recv() {
+            page_loading = false;
+            match page {
+                Ok(events) => {
+                    // Nothing from before the boundary second means there is nothing older
+                    history_exhausted = events.iter().all(|event| event.created_at.as_u64() >= page_boundary);
+                    oldest_fetched = events.iter().map(|event| event.created_at.as_u64()).chain(oldest_fetched).min();
+                    let (new_posts, _) = add_new_events(stateful_list, &mut known_ids, events, min_pow, &mut messages);
+                    spawn_profile_fetch(Arc::clone(&client), stale_authors(&new_posts, &profiles, profile_ttl), profile_tx.clone(), ui_tx.clone());
+                    spawn_repost_fetch(Arc::clone(&client), &new_posts, repost_tx.clone(), ui_tx.clone());
+                },
+                Err(e) => {
+                    messages.push(tui::UiMessage::Error(String::from("Error fetching older posts"), e));
+                    page_retry_at = Some(std::time::Instant::now() + PAGE_RETRY_DELAY);
+                },
+            }
+        }
+
+        let near_bottom = stateful_list.state.selected()
+            .is_none_or(|selected| selected + PAGE_TRIGGER_DISTANCE >= stateful_list.items.len());
+        let page_retry_due = page_retry_at.is_none_or(|at| std::time::Instant::now() >= at);
+        if thread_view.is_none() && near_bottom && !page_loading && !history_exhausted && page_retry_due {
+            page_loading = true;
+            // Pages hidden entirely by the PoW filter still move the window back
+            page_boundary = stateful_list.items.last()
+                .map(|post| post.timestamp)
+                .into_iter()
+                .chain(oldest_fetched)
+                .min()
+                .unwrap_or_else(|| Timestamp::now().as_u64());
+            spawn_page_fetch(Arc::clone(&client), page_boundary, page_tx.clone());
+        }
+
+        if let Ok(fetched) = profile_rx.try_recv() {
+            profiles.merge(fetched);
+            if let Err(e) = profiles.save() {
+                messages.push(tui::UiMessage::Error(String::from("Failed to save profiles"), e));
+            }
+        }
+
+        while let Ok(events) = repost_rx.try_recv() {
+            let originals: Vec<Post> = events.iter()
+                .filter(|event| event.kind == Kind::TextNote)
+                .map(Post::from_event)
+                .collect();
+            nip18::fill_reposts(&mut stateful_list.items, &originals);
+            if let Err(e) = cache::save_reposted_events(events) {
+                messages.push(tui::UiMessage::Error(String::from("Error caching reposted notes"), e));
+            }
+            spawn_profile_fetch(Arc::clone(&client), stale_authors(&originals, &profiles, profile_ttl), profile_tx.clone(), ui_tx.clone());
+        }
+
+        while let Ok(events) = notify_rx.try_recv() {
+            let added = notifications.add_events(&events, &client.my_key());
+            if added > 0 {
+                let ids: HashSet<String> = events.iter().map(|event| event.id.to_hex()).collect();
+                let arrived: Vec<&Notification> = notifications.list(None).into_iter()
+                    .filter(|notification| ids.contains(&notification.id))
+                    .collect();
+                let posts: Vec<Post> = arrived.iter().filter_map(|notification| notification.post.clone()).collect();
+                let senders: Vec<PublicKey> = arrived.iter()
+                    .filter_map(|notification| PublicKey::from_hex(&notification.author).ok())
+                    .collect();
+                let mut authors = profiles.stale(&senders, profile_ttl);
+                authors.extend(stale_authors(&posts, &profiles, profile_ttl));
+                spawn_profile_fetch(Arc::clone(&client), authors, profile_tx.clone(), ui_tx.clone());
+                if let Some(view) = &mut notifications_view {
+                    view.clamp_selection(&notifications);
+                }
+                if let Err(e) = notifications.save() {
+                    messages.push(tui::UiMessage::Error(String::from("Error saving notifications"), e));
+                }
+            }
+        }
+
+        while let Ok(events) = reaction_rx.try_recv() {
+            reactions.add_events(events, &client.my_key());
+        }
+
+        let visible: Vec<&Post> = match &thread_view {
+            Some(thread_view) => thread_view.posts.iter().map(|(_, post)| post).collect(),
+            None => {
+                let selected = stateful_list.state.selected().unwrap_or(0);
+                stateful_list.items.iter()
+                    .skip(selected.saturating_sub(REACTION_WINDOW))
+                    .take(REACTION_WINDOW * 2 + 1)
+                    .collect()
+            },
+        };
+        let now = std::time::Instant::now();
+        let due: Vec<String> = visible.into_iter()
+            .map(Post::target)
+            .filter(|post| reactions_requested.get(&post.id).is_none_or(|at| now.duration_since(*at) >= REACTION_REFRESH))
+            .map(|post| post.id.clone())
+            .collect();
+        if !due.is_empty() {
+            for id in &due {
+                reactions_requested.insert(id.clone(), now);
+            }
+            spawn_reaction_fetch(Arc::clone(&client), due, reaction_tx.clone(), ui_tx.clone());
+        }
+
+        if outbox_rx.try_recv().is_ok() {
+            match outbox::load_outbox() {
+                Ok(entries) => {
+                    outbox_entries = entries;
+                    if let Some(outbox_view) = &mut outbox_view {
+                        outbox_view.set_entries(outbox_entries.clone());
+                    }
+                },
+                Err(e) => messages.push(tui::UiMessage::Error(String::from("Error loading outbox"), e)),
+            }
+        }
+
         if event::poll(std::time::Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
+                if let Some(view) = &mut message_log {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Char('m') => message_log = None,
+                        KeyCode::Down | KeyCode::Char('j') => view.next(&messages),
+                        KeyCode::Up | KeyCode::Char('k') => view.previous(),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if let Some(view) = &mut relay_view {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Char('s') => relay_view = None,
+                        KeyCode::Down | KeyCode::Char('j') => view.next(),
+                        KeyCode::Up | KeyCode::Char('k') => view.previous(),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if let Some(view) = &mut outbox_view {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => outbox_view = None,
+                        KeyCode::Down | KeyCode::Char('j') => view.next(),
+                        KeyCode::Up | KeyCode::Char('k') => view.previous(),
+                        KeyCode::Char('r') => {
+                            if let Some(entry) = view.selected() {
+                                if let Err(e) = outbox::reschedule(&entry.event.id) {
+                                    messages.push(tui::UiMessage::Error(String::from("Error rescheduling note"), e));
+                                }
+                                let task_client = Arc::clone(&client);
+                                let task_tx = outbox_tx.clone();
+                                let task_ui_tx = ui_tx.clone();
+                                tokio::spawn(async move {
+                                    match outbox::flush(&task_client).await {
+                                        Ok(0) => {},
+                                        Ok(sent) => {
+                                            let _ = task_ui_tx.send(tui::UiMessage::Info(format!("Sent {} note(s) from the outbox", sent)));
+                                        },
+                                        Err(e) => {
+                                            let _ = task_ui_tx.send(tui::UiMessage::Error(String::from("Error flushing outbox"), e));
+                                        },
+                                    }
+                                    let _ = task_tx.try_send(());
+                                });
+                            }
+                        },
+                        KeyCode::Char('d') => {
+                            if let Some(entry) = view.selected() {
+                                if let Err(e) = outbox::discard(&entry.event.id) {
+                                    messages.push(tui::UiMessage::Error(String::from("Error discarding note"), e));
+                                }
+                                let _ = outbox_tx.try_send(());
+                            }
+                        },
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if let Some(picker) = &mut reaction_picker {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => reaction_picker = None,
+                        KeyCode::Left | KeyCode::Char('h') | KeyCode::Up | KeyCode::Char('k') => picker.previous(),
+                        KeyCode::Right | KeyCode::Char('l') | KeyCode::Down | KeyCode::Char('j') => picker.next(),
+                        KeyCode::Char(c) if c.is_ascii_digit() => {
+                            picker.select_number(c.to_digit(10).unwrap_or(0) as usize);
+                        },
+                        KeyCode::Enter => {
+                            mining_jobs.push(spawn_reaction(Arc::clone(&client), picker.post.clone(), picker.choice().to_string(), reaction_tx.clone(), outbox_tx.clone(), ui_tx.clone()));
+                            reaction_picker = None;
+                        },
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if let Some(view) = &mut notifications_view {
+                    let mut changed = false;
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Char('N') => notifications_view = None,
+                        KeyCode::Down | KeyCode::Char('j') => view.next(&notifications),
+                        KeyCode::Up | KeyCode::Char('k') => view.previous(),
+                        KeyCode::Tab | KeyCode::Char('l') | KeyCode::Right => view.next_group(&notifications),
+                        KeyCode::BackTab | KeyCode::Char('h') | KeyCode::Left => view.previous_group(&notifications),
+                        KeyCode::Char('a') => {
+                            notifications.mark_all_read(view.group);
+                            changed = true;
+                        },
+                        KeyCode::Enter => {
+                            // Replies and mentions open their thread, the rest the note of ours they're about
+                            let post = view.selected(&notifications).and_then(|notification| {
+                                notification.post.clone().or_else(|| {
+                                    notification.target.as_deref().and_then(|id| cache::get_cached_post(id).ok().flatten())
+                                })
+                            });
+                            if let Some(id) = view.selected(&notifications).map(|notification| notification.id.clone()) {
+                                changed = notifications.mark_read(&id);
+                            }
+                            match post {
+                                Some(post) => {
+                                    let mut thread = tui::ThreadView::new(vec![post.clone()], &post.id);
+                                    thread.loading = true;
+                                    thread_view = Some(thread);
+                                    thread_load_id += 1;
+                                    thread_task = Some(spawn_thread_load(Arc::clone(&client), post, thread_load_id, thread_tx.clone()));
+                                    notifications_view = None;
+                                },
+                                None => messages.push(tui::UiMessage::Info(String::from("The note this is about isn't cached"))),
+                            }
+                        },
+                        _ => {}
+                    }
+                    // Whatever is selected has been seen
+                    if let Some(view) = &notifications_view {
+                        if let Some(id) = view.selected(&notifications).map(|notification| notification.id.clone()) {
+                            changed |= notifications.mark_read(&id);
+                        }
+                    }
+                    if changed {
+                        if let Err(e) = notifications.save() {
+                            messages.push(tui::UiMessage::Error(String::from("Error saving notifications"), e));
+                        }
+                    }
+                    continue;
+                }
+
+                if let Some(view) = &mut drafts_view {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Char('D') => drafts_view = None,
+                        KeyCode::Down | KeyCode::Char('j') => view.next(),
+                        KeyCode::Up | KeyCode::Char('k') => view.previous(),
+                        KeyCode::Enter => {
+                            if let Some(selected) = view.selected() {
+                                let parent = selected.reply_to.as_ref()
+                                    .map(|reply_to| reply_to.reply.id.as_str())
+                                    .or(selected.quote.as_deref())
+                                    .and_then(|id| cache::get_cached_post(id).ok().flatten());
+                                let mut resumed = tui::Composer::new(parent, selected.reply_to.clone());
+                                resumed.quote = selected.quote.clone();
+                                resumed.set_text(&selected.content);
+                                composer = Some(resumed);
+                                drafts_view = None;
+                            }
+                        },
+                        KeyCode::Char('p') => {
+                            if let Some(selected) = view.selected() {
+                                let key = selected.key();
+                                mining_jobs.push(spawn_post(Arc::clone(&client), selected.content.clone(), selected.reply_to.clone(), key.clone(), outbox_tx.clone(), ui_tx.clone()));
+                                // The draft stays saved until the note is signed, just don't offer it twice
+                                let mut remaining = draft::load_drafts().unwrap_or_default();
+                                remaining.retain(|draft| draft.key() != key);
+                                view.set_drafts(remaining);
+                            }
+                        },
+                        KeyCode::Char('d') => {
+                            if let Some(selected) = view.selected() {
+                                if let Err(e) = draft::delete_draft(&selected.key()) {
+                                    messages.push(tui::UiMessage::Error(String::from("Error deleting draft"), e));
+                                }
+                                view.set_drafts(draft::load_drafts().unwrap_or_default());
+                            }
+                        },
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if let Some(view) = &mut composer {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match (view.mode, key.code) {
+                        (tui::ComposerMode::Confirm, KeyCode::Char('y') | KeyCode::Enter) => {
+                            mining_jobs.push(spawn_post(Arc::clone(&client), view.text(), view.reply_to.clone(), view.draft_key(), outbox_tx.clone(), ui_tx.clone()));
+                            composer = None;
+                        },
+                        (tui::ComposerMode::Confirm, _) => view.mode = tui::ComposerMode::Editing,
+                        (_, KeyCode::Char('s')) if ctrl => {
+                            if view.is_blank() {
+                                messages.push(tui::UiMessage::Info(String::from("Nothing to send, the note is empty")));
+                            } else {
+                                view.mode = tui::ComposerMode::Confirm;
+                            }
+                        },
+                        (tui::ComposerMode::Editing, KeyCode::Char('p')) if ctrl => view.mode = tui::ComposerMode::Preview,
+                        (tui::ComposerMode::Preview, KeyCode::Char('p') | KeyCode::Esc) => view.mode = tui::ComposerMode::Editing,
+                        (tui::ComposerMode::Preview, _) => {},
+                        (_, KeyCode::Esc) => {
+                            match draft::save_draft(&view.text(), view.reply_to.as_ref(), view.quote.as_deref()) {
+                                Ok(()) if !view.is_blank() => messages.push(tui::UiMessage::Info(String::from("Draft saved, D lists drafts"))),
+                                Ok(()) => {},
+                                Err(e) => messages.push(tui::UiMessage::Error(String::from("Error saving draft"), e)),
+                            }
+                            composer = None;
+                        },
+                        (_, KeyCode::Char('e')) if ctrl => {
+                            let text = view.text();
+                            let started = Timestamp::now().as_u64();
+                            match tui::with_restored_terminal(terminal, || edit_in_editor(&text, view.reply_to.as_ref(), view.quote.as_deref()))? {
+                                Ok(edited) => view.set_text(&edited),
+                                Err(e) => {
+                                    // Pick up what the editor left behind if it was kept as a draft
+                                    if let Ok(Some(kept)) = draft::get_draft(&view.draft_key()) {
+                                        if kept.updated_at >= started {
+                                            view.set_text(&kept.content);
+                                        }
+                                    }
+                                    messages.push(tui::UiMessage::Error(String::from("Error running $EDITOR"), e));
+                                },
+                            }
+                        },
+                        (_, KeyCode::Char(_)) if ctrl => {},
+                        (_, KeyCode::Char(c)) => view.insert(c),
+                        (_, KeyCode::Enter) => view.newline(),
+                        (_, KeyCode::Backspace) => view.backspace(),
+                        (_, KeyCode::Delete) => view.delete(),
+                        (_, KeyCode::Left) => view.left(),
+                        (_, KeyCode::Right) => view.right(),
+                        (_, KeyCode::Up) => view.up(),
+                        (_, KeyCode::Down) => view.down(),
+                        (_, KeyCode::Home) => view.home(),
+                        (_, KeyCode::End) => view.end(),
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 match key.code {
                     KeyCode::Char('q') => {
                         if thread_view.is_some() {
+                            if let Some(task) = thread_task.take() {
+                                task.abort();
+                            }
                             thread_view = None;
                         } else {
                             return Ok(());
                         }
                     },
                     KeyCode::Esc => {
-                        if thread_view.is_some() {
+                        if let Some(task) = thread_task.take() {
+                            // Stop loading but keep what has arrived so far
+                            task.abort();
+                            if let Some(thread_view) = &mut thread_view {
+                                thread_view.loading = false;
+                            }
+                        } else if thread_view.is_some() {
                             thread_view = None;
                         } else {
                             return Ok(());
@@ -121,83 +649,122 @@ pub async fn run_app(
                             stateful_list.previous();
                         }
                     },
-                    KeyCode::Enter => {
-                        if thread_view.is_none() {
-                            if let Some(selected_post) = stateful_list.items.get(stateful_list.state.selected().unwrap_or(0)) {
-                                if let Some(root_id) = &selected_post.root_id {
-                                    // Fetch the thread
-                                    let thread_posts = client.fetch_thread(root_id).await?;
-                                    thread_view = Some(tui::ThreadView::new(thread_posts));
-                                }
-                            }
+                    KeyCode::Enter if thread_view.is_none() => {
+                        if let Some(selected_post) = selected_post(&thread_view, stateful_list).cloned() {
+                            // Show the post right away and fill in the thread around it as it loads
+                            let mut view = tui::ThreadView::new(vec![selected_post.clone()], &selected_post.id);
+                            view.loading = true;
+                            thread_view = Some(view);
+                            thread_load_id += 1;
+                            thread_task = Some(spawn_thread_load(Arc::clone(&client), selected_post, thread_load_id, thread_tx.clone()));
                         }
                     },
                     KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => stateful_list.jump_down(10),
                     KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => stateful_list.jump_up(10),
+                    KeyCode::Char('x') if !mining_jobs.is_empty() => {
+                        for job in mining_jobs.iter() {
+                            job.cancel();
+                        }
+                    },
+                    KeyCode::Char('s') if thread_view.is_none() => {
+                        relay_view = Some(tui::RelayView::new(client.relay_health().await));
+                        relay_refreshed = std::time::Instant::now();
+                    },
+                    KeyCode::Char('N') => {
+                        let view = tui::NotificationsView::new(&notifications);
+                        if let Some(id) = view.selected(&notifications).map(|notification| notification.id.clone()) {
+                            if notifications.mark_read(&id) {
+                                if let Err(e) = notifications.save() {
+                                    messages.push(tui::UiMessage::Error(String::from("Error saving notifications"), e));
+                                }
+                            }
+                        }
+                        notifications_view = Some(view);
+                    },
+                    KeyCode::Char('D') => match draft::load_drafts() {
+                        Ok(drafts) => drafts_view = Some(tui::DraftsView::new(drafts)),
+                        Err(e) => messages.push(tui::UiMessage::Error(String::from("Error loading drafts"), e)),
+                    },
+                    KeyCode::Char('m') => {
+                        message_log = Some(tui::MessageLogView::new(&messages));
+                    },
+                    KeyCode::Char('o') if thread_view.is_none() => {
+                        outbox_view = Some(tui::OutboxView::new(outbox_entries.clone()));
+                    },
+                    KeyCode::Char('t') if thread_view.is_none() => {
+                        stateful_list.first();
+                        new_above = 0;
+                    },
                     KeyCode::Char('g') => stateful_list.first(),
                     KeyCode::Char('G') => stateful_list.last(),
-                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        if !refresh_in_progress {
-                            refresh_in_progress = true;
-
-                            let last_login = config.get_last_login();
-                            let task_client = Arc::clone(&client);
-                            let task_tx = tx.clone();
-
-                            tokio::spawn(async move {
-                                match fetch_new_posts(&task_client, last_login).await {
-                                    Ok(new_posts) => {
-                                        let _ = task_tx.send(new_posts).await;
-                                    },
-                                    Err(e) => {
-                                        eprintln!("Error fetching notes: {:?}", e);
-                                    }
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) && !refresh_in_progress => {
+                        refresh_in_progress = true;
+
+                        let last_login = config.get_last_login();
+                        let task_client = Arc::clone(&client);
+                        let task_tx = tx.clone();
+                        let task_ui_tx = ui_tx.clone();
+
+                        tokio::spawn(async move {
+                            match fetch_new_posts(&task_client, last_login).await {
+                                Ok(new_events) => {
+                                    let _ = task_tx.send(new_events).await;
+                                },
+                                Err(e) => {
+                                    let _ = task_ui_tx.send(tui::UiMessage::Error(String::from("Error fetching notes"), e));
+                                    // Still end the refresh so Ctrl-r works again
+                                    let _ = task_tx.send(Vec::new()).await;
                                 }
-                            });
-                        }
+                            }
+                        });
                     },
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {},
                     KeyCode::Char('n') => {
-                        // Use the helper function to handle terminal restoration and setup
-                        if let Ok(()) = tui::with_restored_terminal(terminal, || {
-                            // Create and post the note
-                            match create_post_via_editor() {
-                                Ok(note) => {
-                                    let client_clone = Arc::clone(&client);
-                                    tokio::spawn(async move {
-                                        if let Err(e) = post_note(&client_clone, note, None).await {
-                                            eprintln!("Error posting note: {:?}", e);
-                                        }
-                                    });
+                        composer = Some(open_composer(None, None));
+                    },
+                    KeyCode::Char('+') | KeyCode::Char('-') => {
+                        if let Some(selected_post) = selected_post(&thread_view, stateful_list) {
+                            let content = if key.code == KeyCode::Char('+') { "+" } else { "-" };
+                            match my_reaction(&reactions, selected_post) {
+                                Some(mine) => messages.push(tui::UiMessage::Info(format!("Already reacted {} to this note", mine))),
+                                None => mining_jobs.push(spawn_reaction(Arc::clone(&client), selected_post.clone(), content.to_string(), reaction_tx.clone(), outbox_tx.clone(), ui_tx.clone())),
+                            }
+                        }
+                    },
+                    KeyCode::Char('b') => {
+                        if let Some(selected) = selection(&thread_view, stateful_list) {
+                            mining_jobs.push(spawn_repost(Arc::clone(&client), selected.target().clone(), original_event(selected), outbox_tx.clone(), ui_tx.clone()));
+                        }
+                    },
+                    KeyCode::Char('Q') => {
+                        if let Some(selected_post) = selected_post(&thread_view, stateful_list) {
+                            match nip18::nevent_uri(&selected_post.id, Some(&selected_post.pubkey), client.relay_hint()) {
+                                Ok(reference) => {
+                                    let mut view = tui::Composer::quoting(selected_post.clone(), &reference);
+                                    if let Ok(Some(saved)) = draft::get_draft(&view.draft_key()) {
+                                        view.set_text(&saved.content);
+                                    }
+                                    composer = Some(view);
                                 },
-                                Err(e) => eprintln!("Error creating post: {:?}", e),
+                                Err(e) => messages.push(tui::UiMessage::Error(String::from("Error quoting note"), e)),
                             }
-                        }) {
-                            // Terminal was successfully restored and re-setup
                         }
                     },
-                    KeyCode::Char('r') => {
-                        // Reply to the currently selected post
-                        if let Some(selected_post) = stateful_list.items.get(stateful_list.state.selected().unwrap_or(0)) {
-                            if let Ok(()) = tui::with_restored_terminal(terminal, || {
-                                let root_id = selected_post.root_id.clone().unwrap_or_else(|| selected_post.id.clone());
-                                let reply_id = selected_post.id.clone();
-                                
-                                match create_thread_reply_via_editor(root_id, reply_id) {
-                                    Ok((note, reply_to)) => {
-                                        let client_clone = Arc::clone(&client);
-                                        tokio::spawn(async move {
-                                            if let Err(e) = post_note(&client_clone, note, Some(reply_to)).await {
-                                                eprintln!("Error posting reply: {:?}", e);
-                                            }
-                                        });
-                                    },
-                                    Err(e) => eprintln!("Error creating reply: {:?}", e),
-                                }
-                            }) {
-                                // Terminal was successfully restored and re-setup
+                    KeyCode::Char('e') => {
+                        if let Some(selected_post) = selected_post(&thread_view, stateful_list) {
+                            match my_reaction(&reactions, selected_post) {
+                                Some(mine) => messages.push(tui::UiMessage::Info(format!("Already reacted {} to this note", mine))),
+                                None => reaction_picker = Some(tui::ReactionPicker::new(selected_post.clone())),
                             }
                         }
                     },
+                    KeyCode::Char('r') => {
+                        // Reply to the currently selected post, in the thread when one is open
+                        if let Some(selected_post) = selected_post(&thread_view, stateful_list) {
+                            let reply_to = reply_target(&client, selected_post);
+                            composer = Some(open_composer(Some(selected_post.clone()), Some(reply_to)));
+                        }
+                    },
                     /*
                        KeyCode::Enter => {
                     // View post details functionality can be implemented here
@@ -211,42 +778,529 @@ pub async fn run_app(
     }
 }
 
-pub async fn fetch_new_posts(client: &Arc<NostrClient>, last_login: Timestamp) -> Result<Vec<Post>, NostratuiError> {
-    client.fetch_notes_since(last_login).await
+// Periodically retry unsent notes; entries back off on their own schedule
+fn spawn_outbox_retries(client: Arc<NostrClient>, outbox_tx: tokio::sync::mpsc::Sender<()>, ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(OUTBOX_RETRY_INTERVAL).await;
+            match outbox::flush(&client).await {
+                Ok(_) => {
+                    let _ = outbox_tx.try_send(());
+                },
+                Err(e) => {
+                    let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error flushing outbox"), e));
+                },
+            }
+        }
+    });
+}
+
+/// Cache new events and insert the unseen ones into the feed.
+///
+/// Returns the posts added and how many of them landed above the selection.
+fn add_new_events(
+    stateful_list: &mut StatefulList<Post>,
+    known_ids: &mut HashSet<String>,
+    new_events: Vec<nostr_sdk::Event>,
+    min_pow: u8,
+    messages: &mut tui::Messages,
+) -> (Vec<Post>, usize) {
+    let new_posts: Vec<Post> = new_events.iter()
+        .filter(|event| nip18::has_valid_embed(event))
+        .map(Post::from_event)
+        .filter(|post| known_ids.insert(post.id.clone()))
+        .filter(|post| pow::difficulty_of(&post.id) >= min_pow)
+        .collect();
+    // The feed still shows them when caching fails, they are just fetched again next time
+    if let Err(e) = cache::save_events_to_cache(new_events) {
+        messages.push(tui::UiMessage::Error(String::from("Error caching notes"), e));
+    }
+    let inserted_above = stateful_list.insert_sorted_by_key(new_posts.clone(), |post| std::cmp::Reverse(post.timestamp));
+    (new_posts, inserted_above)
+}
+
+/// Mine and publish a note in the background, reporting the outcome in the status bar.
+///
+/// The draft under `draft_key` is deleted once the note is signed, from then on
+/// the outbox keeps it if publishing fails. A cancelled or failed mining run
+/// leaves the draft in place.
+///
+/// Returns the mining job so its progress can be shown and it can be cancelled.
+fn spawn_post(
+    client: Arc<NostrClient>,
+    note: String,
+    reply_to: Option<ReplyTo>,
+    draft_key: String,
+    outbox_tx: tokio::sync::mpsc::Sender<()>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) -> Arc<MiningJob> {
+    let what = if reply_to.is_some() { "reply" } else { "note" };
+    let job = client.mining_job();
+    let task_job = Arc::clone(&job);
+    tokio::spawn(async move {
+        let result = match client.sign_note(note, reply_to, task_job).await {
+            Ok(event) => {
+                if let Err(e) = draft::delete_draft(&draft_key) {
+                    let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error deleting draft"), e));
+                }
+                publish_or_queue(&client, event).await
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(result) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(format!("Published {}, {}", what, describe_publish(&result))));
+            },
+            Err(NostratuiError::Cancelled(_)) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(format!("Mining cancelled, {} not sent", what)));
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(format!("Error posting {}", what), e));
+                let _ = outbox_tx.try_send(());
+            },
+        }
+    });
+    job
+}
+
+/// Mine and publish a repost of `post` in the background, embedding `original` when we have it
+fn spawn_repost(
+    client: Arc<NostrClient>,
+    post: Post,
+    original: Option<nostr_sdk::Event>,
+    outbox_tx: tokio::sync::mpsc::Sender<()>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) -> Arc<MiningJob> {
+    let job = client.light_mining_job();
+    let task_job = Arc::clone(&job);
+    tokio::spawn(async move {
+        let result = match client.sign_repost(&post, original.as_ref(), task_job).await {
+            Ok(event) => publish_or_queue(&client, event).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(result) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(format!("Reposted, {}", describe_publish(&result))));
+            },
+            Err(NostratuiError::Cancelled(_)) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(String::from("Mining cancelled, repost not sent")));
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error reposting"), e));
+                let _ = outbox_tx.try_send(());
+            },
+        }
+    });
+    job
+}
+
+/// Mine and publish a reaction in the background, handing the signed event to the
+/// reaction counts so it shows up right away.
+fn spawn_reaction(
+    client: Arc<NostrClient>,
+    post: Post,
+    content: String,
+    reaction_tx: tokio::sync::mpsc::UnboundedSender<Vec<nostr_sdk::Event>>,
+    outbox_tx: tokio::sync::mpsc::Sender<()>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) -> Arc<MiningJob> {
+    let job = client.light_mining_job();
+    let task_job = Arc::clone(&job);
+    tokio::spawn(async move {
+        let result = match client.sign_reaction(&post, &content, task_job).await {
+            Ok(event) => {
+                let _ = reaction_tx.send(vec![event.clone()]);
+                publish_or_queue(&client, event).await
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(result) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(format!("Reacted {}, {}", content, describe_publish(&result))));
+            },
+            Err(NostratuiError::Cancelled(_)) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(String::from("Mining cancelled, reaction not sent")));
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error sending reaction"), e));
+                let _ = outbox_tx.try_send(());
+            },
+        }
+    });
+    job
+}
+
+fn spawn_reaction_fetch(
+    client: Arc<NostrClient>,
+    ids: Vec<String>,
+    reaction_tx: tokio::sync::mpsc::UnboundedSender<Vec<nostr_sdk::Event>>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) {
+    let ids: Vec<EventId> = ids.iter().filter_map(|id| EventId::from_hex(id).ok()).collect();
+    tokio::spawn(async move {
+        match client.fetch_reactions(&ids).await {
+            Ok(events) => {
+                let _ = reaction_tx.send(events);
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error fetching reactions"), e));
+            },
+        }
+    });
+}
+
+/// Our reaction to `post`, if we already sent one
+fn my_reaction<'a>(reactions: &'a ReactionStore, post: &Post) -> Option<&'a str> {
+    reactions.get(&post.id).and_then(|counts| counts.mine.as_deref())
+}
+
+/// The post under the selection, in the thread when one is open
+fn selection<'a>(thread_view: &'a Option<tui::ThreadView>, stateful_list: &'a StatefulList<Post>) -> Option<&'a Post> {
+    match thread_view {
+        Some(thread_view) => thread_view.selected(),
+        None => stateful_list.items.get(stateful_list.state.selected().unwrap_or(0)),
+    }
+}
+
+/// What actions on the selection apply to: a repost stands for the note it reposts once that is known
+fn selected_post<'a>(thread_view: &'a Option<tui::ThreadView>, stateful_list: &'a StatefulList<Post>) -> Option<&'a Post> {
+    selection(thread_view, stateful_list).map(Post::target)
+}
+
+/// Fetch the reposted notes that `posts` didn't embed and that weren't fetched on an earlier run.
+///
+/// They are cached apart from the feed, which only holds notes from follows.
+fn spawn_repost_fetch(
+    client: Arc<NostrClient>,
+    posts: &[Post],
+    repost_tx: tokio::sync::mpsc::UnboundedSender<Vec<nostr_sdk::Event>>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) {
+    let ids: Vec<EventId> = posts.iter()
+        .filter(|post| post.repost.is_none())
+        .filter_map(|post| post.reposted_id.as_ref())
+        .filter_map(|id| EventId::from_hex(id).ok())
+        .collect();
+    if ids.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        match client.fetch_events_by_id(&ids).await {
+            Ok(events) => {
+                let _ = repost_tx.send(events);
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error fetching reposted notes"), e));
+            },
+        }
+    });
+}
+
+/// The signed event behind what `post` stands for, from the cache or embedded in a cached repost
+fn original_event(post: &Post) -> Option<nostr_sdk::Event> {
+    cache::get_cached_event(&post.target().id).ok().flatten()
+        .or_else(|| post.is_repost()
+            .then(|| cache::get_cached_event(&post.id).ok().flatten())
+            .flatten()
+            .and_then(|event| nip18::embedded_note(&event)))
+}
+
+/// Progress of a background thread load, tagged with the load it belongs to
+enum ThreadUpdate {
+    Posts(u64, Vec<Post>),
+    Finished(u64, Result<(), NostratuiError>),
+}
+
+impl ThreadUpdate {
+    fn load_id(&self) -> u64 {
+        match self {
+            ThreadUpdate::Posts(id, _) | ThreadUpdate::Finished(id, _) => *id,
+        }
+    }
+}
+
+fn spawn_thread_load(
+    client: Arc<NostrClient>,
+    selected: Post,
+    load_id: u64,
+    thread_tx: tokio::sync::mpsc::UnboundedSender<ThreadUpdate>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let posts_tx = thread_tx.clone();
+        let result = client.fetch_thread(&selected, |posts| {
+            let _ = posts_tx.send(ThreadUpdate::Posts(load_id, posts));
+        }).await;
+        let _ = thread_tx.send(ThreadUpdate::Finished(load_id, result));
+    })
+}
+
+/// Fetch the page of history before `boundary`, along with all of the boundary second.
+///
+/// The page itself ends a second before the boundary so a second holding a full page of
+/// events can't stall paging, the rest of that second comes from its own request.
+/// Repeats of events already shown are dropped by id.
+fn spawn_page_fetch(
+    client: Arc<NostrClient>,
+    boundary: u64,
+    page_tx: tokio::sync::mpsc::Sender<Result<Vec<nostr_sdk::Event>, NostratuiError>>,
+) {
+    tokio::spawn(async move {
+        let boundary_second = FeedQuery {
+            since: Some(Timestamp::from_secs(boundary)),
+            until: Some(Timestamp::from_secs(boundary)),
+            ..FeedQuery::default()
+        };
+        let older = FeedQuery {
+            until: Some(Timestamp::from_secs(boundary.saturating_sub(1))),
+            limit: Some(PAGE_SIZE),
+            ..FeedQuery::default()
+        };
+        let page = async {
+            let mut events = Vec::new();
+            for query in [boundary_second, older] {
+                let fetched = client.fetch_notes(&query).await?;
+                // An empty page from relays that all failed isn't the end of history
+                if fetched.all_failed() {
+                    let reason = fetched.relays.iter().find_map(|summary| summary.errors.first()).cloned().unwrap_or_default();
+                    return Err(NostratuiError::Network(format!("No relay answered: {}", reason)));
+                }
+                events.extend(fetched.events);
+            }
+            Ok(events)
+        };
+        let _ = page_tx.send(page.await).await;
+    });
+}
+
+fn spawn_legacy_upgrade(client: Arc<NostrClient>, ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>) {
+    tokio::spawn(async move {
+        match cache::upgrade_legacy_records(&client).await {
+            Ok(0) => {},
+            Ok(upgraded) => {
+                let _ = ui_tx.send(tui::UiMessage::Info(format!("Refetched {} cached post(s) with their signatures", upgraded)));
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error upgrading cached posts"), e));
+            },
+        }
+    });
+}
+
+fn spawn_live_feed(
+    client: Arc<NostrClient>,
+    live_tx: tokio::sync::mpsc::Sender<Vec<nostr_sdk::Event>>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) {
+    tokio::spawn(async move {
+        let (subscription_id, mut notifications) = match client.subscribe_notes(Timestamp::now()).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error subscribing to new notes"), e));
+                return;
+            }
+        };
+
+        loop {
+            match notifications.recv().await {
+                Ok(RelayPoolNotification::Event { subscription_id: id, event, .. }) => {
+                    if id == subscription_id && matches!(event.kind, Kind::TextNote | Kind::Repost)
+                        && live_tx.send(vec![*event]).await.is_err() {
+                        break;
+                    }
+                },
+                Ok(RelayPoolNotification::Shutdown) => break,
+                Ok(_) => {},
+                Err(RecvError::Lagged(skipped)) => log::warn!("Live feed lagged, skipped {} notifications", skipped),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+fn spawn_notifications(
+    client: Arc<NostrClient>,
+    since: Timestamp,
+    notify_tx: tokio::sync::mpsc::Sender<Vec<nostr_sdk::Event>>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) {
+    tokio::spawn(async move {
+        let (subscription_id, mut notifications) = match client.subscribe_notifications(since).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error subscribing to notifications"), e));
+                return;
+            }
+        };
+
+        loop {
+            match notifications.recv().await {
+                Ok(RelayPoolNotification::Event { subscription_id: id, event, .. }) => {
+                    if id == subscription_id && notify_tx.send(vec![*event]).await.is_err() {
+                        break;
+                    }
+                },
+                Ok(RelayPoolNotification::Shutdown) => break,
+                Ok(_) => {},
+                Err(RecvError::Lagged(skipped)) => log::warn!("Notifications lagged, skipped {} events", skipped),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+fn stale_authors(posts: &[Post], profiles: &ProfileStore, ttl: u64) -> Vec<PublicKey> {
+    let authors: Vec<PublicKey> = posts.iter()
+        .flat_map(|post| std::iter::once(post).chain(post.repost.as_deref()))
+        .filter_map(|post| PublicKey::from_hex(&post.pubkey).ok())
+        .collect();
+    profiles.stale(&authors, ttl)
+}
+
+fn spawn_profile_fetch(
+    client: Arc<NostrClient>,
+    authors: Vec<PublicKey>,
+    profile_tx: tokio::sync::mpsc::Sender<ProfileStore>,
+    ui_tx: tokio::sync::mpsc::UnboundedSender<tui::UiMessage>,
+) {
+    if authors.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        match client.fetch_profiles(authors).await {
+            Ok(profiles) => {
+                let _ = profile_tx.send(profiles).await;
+            },
+            Err(e) => {
+                let _ = ui_tx.send(tui::UiMessage::Error(String::from("Error fetching profiles"), e));
+            },
+        }
+    });
+}
+
+pub async fn fetch_new_posts(client: &Arc<NostrClient>, last_login: Timestamp) -> Result<Vec<nostr_sdk::Event>, NostratuiError> {
+    let mut sync = SyncState::load()?;
+    log_sync_gaps(client, &sync, last_login);
+    let fetched = client.sync_notes(&mut sync, last_login).await?;
+    log_relay_summaries(&fetched.relays);
+    sync.save()?;
+    Ok(fetched.events)
+}
+
+fn log_sync_gaps(client: &NostrClient, sync: &SyncState, default_since: Timestamp) {
+    for gap in sync.gaps(&client.get_relays(), &client.contact_keys(), default_since, GAP_THRESHOLD_SECS) {
+        log::info!("{} is {}s behind, backfilling from {}", gap.relay, gap.behind_by, gap.since);
+    }
+}
+
+fn log_relay_summaries(summaries: &[RelayFetchSummary]) {
+    for summary in summaries {
+        log::info!("{}: {} events from {} requests, {} errors", summary.relay, summary.events, summary.requests, summary.errors.len());
+    }
+}
+
+pub async fn post_note(client: &NostrClient, content: String, reply_to: Option<ReplyTo>, job: Arc<MiningJob>) -> Result<PublishResult, NostratuiError> {
+    let event = client.sign_note(content, reply_to, job).await?;
+    publish_or_queue(client, event).await
 }
 
-pub async fn post_note(client: &NostrClient, content: String, reply_to: Option<(String, String)>) -> Result<(), NostratuiError> {
-    client.post_note(content, reply_to).await
+/// Publish a signed event, keeping it in the outbox for later retries if no relay accepts it
+pub async fn publish_or_queue(client: &NostrClient, event: nostr_sdk::Event) -> Result<PublishResult, NostratuiError> {
+    match client.publish_event(&event).await {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            outbox::queue_event(event, e.to_string())?;
+            Err(e)
+        }
+    }
 }
 
-pub fn create_post_via_editor() -> Result<String,NostratuiError> {
+/// One line on how many relays took an event and why the others didn't
+fn describe_publish(result: &PublishResult) -> String {
+    let total = result.accepted.len() + result.rejected.len();
+    let mut description = format!("accepted by {}/{} relays", result.accepted.len(), total);
+    for (relay, reason) in &result.rejected {
+        description.push_str(&format!(", {} rejected: {}", relay, reason));
+    }
+    description
+}
+
+/// Write a note in $EDITOR, starting from the saved draft for `reply_to` or `quote` if there is one.
+///
+/// The text stays saved as that draft until the note is sent, so a failed publish loses nothing.
+pub fn create_post_via_editor(reply_to: Option<&ReplyTo>, quote: Option<&str>) -> Result<String,NostratuiError> {
+    let initial = draft::get_draft(&draft::key_for(reply_to, quote))?
+        .map(|draft| draft.content)
+        .unwrap_or_default();
+    let content = edit_in_editor(&initial, reply_to, quote)?;
+    draft::save_draft(&content, reply_to, quote)?;
+    Ok(content)
+}
+
+/// Let the user edit `initial` in $EDITOR and return what they saved.
+///
+/// When the editor fails, whatever is in the file is kept as the draft for `reply_to` or `quote`.
+pub fn edit_in_editor(initial: &str, reply_to: Option<&ReplyTo>, quote: Option<&str>) -> Result<String, NostratuiError> {
     let editor = env::var("EDITOR")
         .unwrap_or_else(|_| "vi".to_string());
 
-    let mut temp_path = env::temp_dir();
-    temp_path.push("note");
+    let temp_path = create_temp_note(initial)?;
 
     let status = Command::new(editor)
         .arg(&temp_path)
-        .status()?;
-
-    if !status.success() {
-        return Err(NostratuiError::Io(
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Editor exited with non-zero status"
-                ).to_string()
-        ).into());
-    }
+        .status();
 
-    let content = fs::read_to_string(&temp_path)?;
+    let content = match status {
+        Ok(status) if status.success() => fs::read_to_string(&temp_path).map_err(NostratuiError::from),
+        Ok(_) => {
+            let leftover = fs::read_to_string(&temp_path).unwrap_or_default();
+            let message = if !leftover.trim().is_empty() && draft::save_draft(&leftover, reply_to, quote).is_ok() {
+                "Editor exited with non-zero status, text kept as a draft"
+            } else {
+                "Editor exited with non-zero status"
+            };
+            Err(NostratuiError::Io(message.to_string()))
+        },
+        Err(e) => Err(e.into()),
+    };
     let _ = fs::remove_file(&temp_path);
-    Ok(content)
+    content
 }
 
-// Add a new function to handle thread replies
-pub fn create_thread_reply_via_editor(root_id: String, reply_id: String) -> Result<(String, (String, String)), NostratuiError> {
-    let content = create_post_via_editor()?;
-    Ok((content, (root_id, reply_id)))
+// A fresh file per edit, so two instances or a leftover file can't mix up notes
+fn create_temp_note(initial: &str) -> Result<std::path::PathBuf, NostratuiError> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    let mut attempt = 0;
+    loop {
+        let temp_path = env::temp_dir().join(format!("nostratui-note-{}-{}-{}.md", std::process::id(), nanos, attempt));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(mut file) => {
+                std::io::Write::write_all(&mut file, initial.as_bytes())?;
+                return Ok(temp_path);
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// A composer for `reply_to`, holding its saved draft if there is one
+fn open_composer(parent: Option<Post>, reply_to: Option<ReplyTo>) -> tui::Composer {
+    let saved = draft::get_draft(&draft::key_for(reply_to.as_ref(), None)).ok().flatten();
+    let mut composer = tui::Composer::new(parent, reply_to);
+    if let Some(saved) = saved {
+        composer.set_text(&saved.content);
+    }
+    composer
+}
+
+/// Tags for replying to `post`, taken from its cached event when we have it
+fn reply_target(client: &NostrClient, post: &Post) -> ReplyTo {
+    match cache::get_cached_event(&post.id) {
+        Ok(Some(event)) => ReplyTo::from_event(&event, client.relay_hint()),
+        _ => ReplyTo::from_post(post),
+    }
 }
 
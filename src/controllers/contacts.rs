@@ -0,0 +1,170 @@
+use nostr_sdk::prelude::*;
+
+use crate::models::{NostrClient, Config, ProfileStore};
+use crate::error::NostratuiError;
+
+pub async fn list_contacts(client: &NostrClient, config: &Config) -> Result<(), NostratuiError> {
+    let relay_follows: Vec<(PublicKey, Option<String>)> = client.fetch_contact_list().await?
+        .map(|event| event.tags.iter().filter_map(followed_key).collect())
+        .unwrap_or_default();
+
+    let mut rows: Vec<(String, String, &str)> = Vec::new();
+    for (key, name) in &config.contacts {
+        let public_key = PublicKey::parse(key)
+            .map_err(|e| NostratuiError::KeyParsing(e.to_string()))?;
+        let source = if relay_follows.iter().any(|(pk, _)| *pk == public_key) {
+            "config, relay"
+        } else {
+            "config"
+        };
+        rows.push((name.clone(), to_npub(&public_key), source));
+    }
+    for (public_key, petname) in &relay_follows {
+        let npub = to_npub(public_key);
+        if !rows.iter().any(|(_, n, _)| *n == npub) {
+            rows.push((petname.clone().unwrap_or_default(), npub, "relay"));
+        }
+    }
+
+    let name_width = rows.iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+    println!("{:<name_width$}  {:<63}  SOURCE", "NAME", "NPUB");
+    for (name, npub, source) in rows {
+        println!("{:<name_width$}  {:<63}  {}", name, npub, source);
+    }
+    Ok(())
+}
+
+pub async fn follow_contact(
+    client: &NostrClient,
+    config: &mut Config,
+    pubkey: &str,
+    name: Option<String>,
+    relay: Option<String>,
+    init: bool,
+) -> Result<(), NostratuiError> {
+    let public_key = PublicKey::parse(pubkey)
+        .map_err(|e| NostratuiError::KeyParsing(e.to_string()))?;
+    let (mut tags, content) = current_contact_list(client, config, init).await?;
+
+    if tags.iter().any(|tag| is_follow_of(tag, &public_key)) {
+        println!("Already following {}", to_npub(&public_key));
+    } else {
+        let hex = public_key.to_hex();
+        let mut values = vec!["p".to_string(), hex];
+        // Relay hint and petname are positional, so an empty hint is kept when only a name is given
+        if relay.is_some() || name.is_some() {
+            values.push(relay.unwrap_or_default());
+        }
+        if let Some(petname) = &name {
+            values.push(petname.clone());
+        }
+        let tag = Tag::parse(values)
+            .map_err(|e| NostratuiError::NostrSdk(e.to_string()))?;
+        tags.push(tag);
+        check_not_shrinking(&tags, config, None)?;
+        client.publish_contact_list(tags, content).await?;
+        println!("Followed {}", to_npub(&public_key));
+    }
+
+    // An empty config means the feed follows the relay list, adding one key would narrow it to that key
+    if !config.contacts.is_empty() && find_config_contact(config, &public_key).is_none() {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                let mut profiles = ProfileStore::load(&[])?;
+                profiles.merge(client.fetch_profiles(vec![public_key]).await?);
+                profiles.save()?;
+                profiles.display_name_for(&public_key)
+            },
+        };
+        config.contacts.push((to_npub(&public_key), name));
+    }
+
+    // Only the config changes here, the client's contacts are for feed commands
+    config.save()
+}
+
+pub async fn unfollow_contact(client: &NostrClient, config: &mut Config, pubkey: &str, init: bool) -> Result<(), NostratuiError> {
+    let public_key = PublicKey::parse(pubkey)
+        .map_err(|e| NostratuiError::KeyParsing(e.to_string()))?;
+    let (mut tags, content) = current_contact_list(client, config, init).await?;
+
+    let followed = tags.len();
+    tags.retain(|tag| !is_follow_of(tag, &public_key));
+    if tags.len() == followed {
+        println!("Not following {}", to_npub(&public_key));
+    } else {
+        check_not_shrinking(&tags, config, Some(&public_key))?;
+        client.publish_contact_list(tags, content).await?;
+        println!("Unfollowed {}", to_npub(&public_key));
+    }
+
+    if let Some(index) = find_config_contact(config, &public_key) {
+        config.contacts.remove(index);
+    }
+
+    // Only the config changes here, the client's contacts are for feed commands
+    config.save()
+}
+
+// Start from the published list so unrelated tags, petnames and relay hints survive.
+// A missing list may just be slow relays, so a new one is only seeded from the config with `init`.
+async fn current_contact_list(client: &NostrClient, config: &Config, init: bool) -> Result<(Vec<Tag>, String), NostratuiError> {
+    match client.fetch_contact_list().await? {
+        Some(event) => Ok((event.tags.to_vec(), event.content)),
+        None if !init => Err(NostratuiError::Network(
+            "No contact list found on relays, publishing would replace it; pass --init to start a new one from the config".to_string()
+        )),
+        None => {
+            log::warn!("No contact list found on relays, seeding from config");
+            let tags = config.contacts.iter()
+                .filter_map(|(key, _)| PublicKey::parse(key).ok())
+                .map(Tag::public_key)
+                .collect();
+            Ok((tags, String::new()))
+        }
+    }
+}
+
+// Refuse to publish a list following fewer keys than the config, other than the one being unfollowed
+fn check_not_shrinking(tags: &[Tag], config: &Config, unfollowed: Option<&PublicKey>) -> Result<(), NostratuiError> {
+    let follows = tags.iter().filter(|tag| followed_key(tag).is_some()).count();
+    let known = config.contacts.iter()
+        .filter_map(|(key, _)| PublicKey::parse(key).ok())
+        .filter(|key| Some(key) != unfollowed)
+        .count();
+    if follows < known {
+        return Err(NostratuiError::Network(format!(
+            "The contact list on relays follows {} keys but the config has {}, not publishing a smaller list",
+            follows, known
+        )));
+    }
+    Ok(())
+}
+
+fn followed_key(tag: &Tag) -> Option<(PublicKey, Option<String>)> {
+    let values = tag.as_slice();
+    if values.first().map(String::as_str) != Some("p") {
+        return None;
+    }
+    let public_key = PublicKey::from_hex(values.get(1)?).ok()?;
+    let petname = values.get(3).filter(|name| !name.is_empty()).cloned();
+    Some((public_key, petname))
+}
+
+fn is_follow_of(tag: &Tag, public_key: &PublicKey) -> bool {
+    followed_key(tag).is_some_and(|(pk, _)| pk == *public_key)
+}
+
+fn find_config_contact(config: &Config, public_key: &PublicKey) -> Option<usize> {
+    config.contacts.iter()
+        .position(|(key, _)| PublicKey::parse(key).is_ok_and(|pk| pk == *public_key))
+}
+
+fn to_npub(public_key: &PublicKey) -> String {
+    public_key.to_bech32().unwrap_or_else(|_| public_key.to_hex())
+}
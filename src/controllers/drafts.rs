@@ -0,0 +1,52 @@
+use chrono::{Local, TimeZone};
+use crate::controllers::app::create_post_via_editor;
+use crate::controllers::post::publish_note;
+use crate::models::{Draft, NostrClient, draft};
+use crate::error::NostratuiError;
+
+/// Print each draft's key, when it was last edited and its first line
+pub fn list_drafts() -> Result<(), NostratuiError> {
+    let drafts = draft::load_drafts()?;
+    if drafts.is_empty() {
+        eprintln!("No drafts");
+        return Ok(());
+    }
+
+    let key_width = drafts.iter()
+        .map(|draft| draft.display_key().len())
+        .max()
+        .unwrap_or(0);
+    for draft in &drafts {
+        let edited = Local.timestamp_opt(draft.updated_at as i64, 0)
+            .single()
+            .map_or_else(String::new, |time| time.format("%Y-%m-%d %H:%M").to_string());
+        let first_line = draft.content.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+        println!("{:<key_width$}  {}  {}", draft.display_key(), edited, first_line);
+    }
+    Ok(())
+}
+
+/// Open a draft in $EDITOR and publish what comes back
+pub async fn resume_draft(client: &NostrClient, target: &str) -> Result<(), NostratuiError> {
+    let draft = find_draft(target)?;
+    let note = create_post_via_editor(draft.reply_to.as_ref(), draft.quote.as_deref())?;
+    publish_note(client, note, draft.reply_to, draft.quote, false).await
+}
+
+pub async fn publish_draft(client: &NostrClient, target: &str) -> Result<(), NostratuiError> {
+    let draft = find_draft(target)?;
+    publish_note(client, draft.content, draft.reply_to, draft.quote, false).await
+}
+
+pub fn delete_draft(target: &str) -> Result<(), NostratuiError> {
+    let draft = find_draft(target)?;
+    draft::delete_draft(&draft.key())?;
+    eprintln!("Deleted draft {}", draft.display_key());
+    Ok(())
+}
+
+fn find_draft(target: &str) -> Result<Draft, NostratuiError> {
+    let key = draft::parse_key(target)?;
+    draft::get_draft(&key)?
+        .ok_or_else(|| NostratuiError::Config(format!("No draft for {}", target)))
+}
@@ -0,0 +1,150 @@
+use nostr_sdk::prelude::*;
+
+use crate::cli::OutputFormat;
+use crate::models::{NostrClient, Config, FeedQuery, Post, ProfileStore, SyncState};
+use crate::models::sync::GAP_THRESHOLD_SECS;
+use crate::models::{cache, nip18, pow};
+use crate::error::NostratuiError;
+
+pub async fn fetch_command(
+    client: &mut NostrClient,
+    config: &mut Config,
+    mut query: FeedQuery,
+    format: OutputFormat,
+    no_cache: bool,
+) -> Result<(), NostratuiError> {
+    client.set_contacts(config.contacts.clone()).await?;
+    if config.contacts.is_empty() {
+        config.contacts = client.get_contacts()
+            .into_iter()
+            .map(|c| c.to_string_tuple())
+            .collect();
+    }
+
+    // The default window syncs from each relay's cursors, explicit windows and limits are one-off queries
+    let default_window = query.since.is_none() && query.until.is_none() && query.authors.is_empty() && query.limit.is_none();
+    let mut sync = SyncState::load()?;
+    let fetched = if default_window {
+        let default_since = config.get_last_login();
+        for gap in sync.gaps(&client.get_relays(), &client.contact_keys(), default_since, GAP_THRESHOLD_SECS) {
+            eprintln!("{} is {} behind, backfilling", gap.relay, format_duration(gap.behind_by));
+        }
+        client.sync_notes(&mut sync, default_since).await?
+    } else {
+        if query.since.is_none() && query.until.is_none() {
+            query.since = Some(config.get_last_login());
+        }
+        client.fetch_notes(&query).await?
+    };
+    // The cache keeps everything, like the TUI only notes with enough work are shown
+    let mut posts: Vec<Post> = fetched.events.iter()
+        .filter(|event| pow::difficulty_of(&event.id.to_hex()) >= config.min_incoming_pow)
+        .filter(|event| nip18::has_valid_embed(event))
+        .map(Post::from_event)
+        .collect();
+    let batch: Vec<Post> = fetched.events.iter()
+        .filter(|event| event.kind == Kind::TextNote)
+        .map(Post::from_event)
+        .collect();
+    nip18::fill_reposts(&mut posts, &batch);
+    if let Err(e) = fill_reposts(client, &mut posts, !no_cache).await {
+        log::warn!("Failed to fetch reposted notes: {}", e);
+    }
+
+    // Keep stdout for the posts so the summary doesn't break JSON output
+    for summary in &fetched.relays {
+        match summary.errors.first() {
+            Some(error) => eprintln!("{}: {} events, {} of {} requests failed ({})", summary.relay, summary.events, summary.errors.len(), summary.requests, error),
+            None => eprintln!("{}: {} events", summary.relay, summary.events),
+        }
+    }
+
+    if !no_cache {
+        cache::save_events_to_cache(fetched.events)?;
+        if default_window {
+            config.update_last_login();
+            sync.save()?;
+        }
+        config.save()?;
+    }
+
+    let mut profiles = ProfileStore::load(&config.contacts)?;
+    if format == OutputFormat::Table {
+        // Authors passed with --author may not be contacts, name them from their metadata
+        let authors: Vec<PublicKey> = posts.iter()
+            .flat_map(|post| [Some(post), post.repost.as_deref()])
+            .flatten()
+            .filter_map(|post| PublicKey::from_hex(&post.pubkey).ok())
+            .collect();
+        let stale = profiles.stale(&authors, config.profile_ttl);
+        match client.fetch_profiles(stale).await {
+            Ok(fetched) => {
+                profiles.merge(fetched);
+                profiles.save()?;
+            },
+            Err(e) => log::warn!("Failed to fetch profiles: {}", e),
+        }
+    }
+
+    print_posts(&posts, &profiles, format)
+}
+
+/// Fill in the notes reposts didn't embed, from earlier fetches or else the relays.
+///
+/// What the relays return is kept for next time when `save` is set.
+pub async fn fill_reposts(client: &NostrClient, posts: &mut [Post], save: bool) -> Result<(), NostratuiError> {
+    cache::fill_cached_reposts(posts)?;
+    let missing: Vec<EventId> = posts.iter()
+        .filter(|post| post.repost.is_none())
+        .filter_map(|post| post.reposted_id.as_ref())
+        .filter_map(|id| EventId::from_hex(id).ok())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let events = client.fetch_events_by_id(&missing).await?;
+    let originals: Vec<Post> = events.iter()
+        .filter(|event| event.kind == Kind::TextNote)
+        .map(Post::from_event)
+        .collect();
+    nip18::fill_reposts(posts, &originals);
+    if save {
+        cache::save_reposted_events(events)?;
+    }
+    Ok(())
+}
+
+fn print_posts(posts: &[Post], profiles: &ProfileStore, format: OutputFormat) -> Result<(), NostratuiError> {
+    match format {
+        OutputFormat::Table => {
+            let names: Vec<String> = posts.iter()
+                .map(|post| profiles.byline(post))
+                .collect();
+            let user_width = names.iter()
+                .map(|name| name.chars().count())
+                .max()
+                .unwrap_or(0);
+            for (post, name) in posts.iter().zip(names) {
+                let first_line = post.content.lines().next().unwrap_or_default();
+                let excerpt: String = first_line.chars().take(80).collect();
+                println!("{:<18}  {:<user_width$}  {}", post.datetime, name, excerpt);
+            }
+        },
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(posts)?),
+        OutputFormat::Jsonl => {
+            for post in posts {
+                println!("{}", serde_json::to_string(post)?);
+            }
+        },
+    }
+    Ok(())
+}
+
+fn format_duration(secs: u64) -> String {
+    match secs {
+        s if s >= 60 * 60 * 24 => format!("{}d", s / (60 * 60 * 24)),
+        s if s >= 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}m", s / 60),
+    }
+}
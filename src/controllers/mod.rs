@@ -1,4 +1,16 @@
 pub mod app;
+pub mod stream;
+pub mod contacts;
+pub mod post;
+pub mod fetch;
+pub mod relays;
+pub mod drafts;
 
 // Re-export key functions
 pub use app::{start_app,init_feed,create_post_via_editor,post_note};
+pub use stream::stream_notes;
+pub use contacts::{list_contacts, follow_contact, unfollow_contact};
+pub use post::{post_command, repost_command};
+pub use fetch::fetch_command;
+pub use relays::relays_command;
+pub use drafts::{list_drafts, resume_draft, publish_draft, delete_draft};
@@ -0,0 +1,156 @@
+use std::io::{self, IsTerminal, Read};
+use std::sync::Arc;
+use std::time::Duration;
+use nostr_sdk::prelude::*;
+
+use crate::controllers::app::{create_post_via_editor, publish_or_queue};
+use crate::models::{NostrClient, Post, ReplyTo, draft, nip18, outbox};
+use crate::models::pow::MiningJob;
+use crate::error::NostratuiError;
+
+pub async fn post_command(
+    client: &NostrClient,
+    content: Option<String>,
+    reply_to: Option<String>,
+    quote: Option<String>,
+    dry_run: bool,
+) -> Result<(), NostratuiError> {
+    let reply = match reply_to {
+        Some(target) => Some(client.resolve_reply_target(&target).await?),
+        None => None,
+    };
+    let quote = match quote {
+        Some(target) => Some(quote_reference(client, &target).await?),
+        None => None,
+    };
+    let quote_id = quote.as_ref().map(|(id, _)| id.clone());
+
+    let mut note = match content.as_deref() {
+        Some("-") => read_stdin()?,
+        Some(content) => content.to_string(),
+        None if !io::stdin().is_terminal() => read_stdin()?,
+        None => create_post_via_editor(reply.as_ref(), quote_id.as_deref())?,
+    };
+    if let Some((_, reference)) = &quote {
+        if !note.contains(reference.as_str()) {
+            note = format!("{}\n\n{}", note.trim_end(), reference).trim_start().to_string();
+        }
+    }
+
+    publish_note(client, note, reply, quote_id, dry_run).await
+}
+
+/// Repost a note by its note, nevent or hex id, embedding the original as NIP-18 asks
+pub async fn repost_command(client: &NostrClient, target: String, dry_run: bool) -> Result<(), NostratuiError> {
+    let (event_id, original, _) = client.resolve_event(&target).await?;
+    let original = original
+        .ok_or_else(|| NostratuiError::Network(format!("Note {} not found on relays", event_id.to_hex())))?;
+
+    let job = client.light_mining_job();
+    let event = sign_with_progress(&job, client.sign_repost(&Post::from_event(&original), Some(&original), Arc::clone(&job))).await?;
+    if dry_run {
+        println!("{}", event.as_json());
+        return Ok(());
+    }
+    publish_and_report(client, event).await
+}
+
+/// The hex id of the note `target` refers to, and a `nostr:nevent` reference to put in the text
+async fn quote_reference(client: &NostrClient, target: &str) -> Result<(String, String), NostratuiError> {
+    let (event_id, event, hint) = client.resolve_event(target).await?;
+    let author = event.map(|event| event.pubkey.to_hex());
+    let reference = nip18::nevent_uri(&event_id.to_hex(), author.as_deref(), hint.or_else(|| client.relay_hint()))?;
+    Ok((event_id.to_hex(), reference))
+}
+
+/// Sign, publish and report a note, dropping the draft for its reply or quote target once it's signed
+pub async fn publish_note(
+    client: &NostrClient,
+    note: String,
+    reply: Option<ReplyTo>,
+    quote: Option<String>,
+    dry_run: bool,
+) -> Result<(), NostratuiError> {
+    if note.trim().is_empty() {
+        return Err(NostratuiError::Io("Note is empty, nothing to post".to_string()));
+    }
+
+    let draft_key = draft::key_for(reply.as_ref(), quote.as_deref());
+    let job = client.mining_job();
+    let event = sign_with_progress(&job, client.sign_note(note, reply, Arc::clone(&job))).await?;
+    if dry_run {
+        println!("{}", event.as_json());
+        return Ok(());
+    }
+    // From here on the outbox keeps the note if publishing fails
+    draft::delete_draft(&draft_key)?;
+
+    publish_and_report(client, event).await
+}
+
+/// Publish a signed event and print how each relay took it
+async fn publish_and_report(client: &NostrClient, event: Event) -> Result<(), NostratuiError> {
+    // Give earlier notes that never made it out another chance first
+    match outbox::flush(client).await {
+        Ok(0) => {},
+        Ok(published) => eprintln!("Published {} queued note(s) from the outbox", published),
+        Err(e) => log::warn!("Failed to flush outbox: {}", e),
+    }
+
+    let result = match publish_or_queue(client, event).await {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Note kept in the outbox, it will be retried by the TUI");
+            return Err(e);
+        }
+    };
+
+    // Per-relay results go to stderr so stdout stays just the note id
+    for relay in &result.accepted {
+        eprintln!("ok        {}", relay);
+    }
+    for (relay, reason) in &result.rejected {
+        eprintln!("rejected  {}: {}", relay, reason);
+    }
+    println!("{}", result.event_id.to_bech32().unwrap_or_else(|_| result.event_id.to_hex()));
+    Ok(())
+}
+
+/// Wait for `signing` to mine and sign, showing progress on stderr when it's a terminal
+async fn sign_with_progress(
+    job: &MiningJob,
+    signing: impl std::future::Future<Output = Result<Event, NostratuiError>>,
+) -> Result<Event, NostratuiError> {
+    if job.difficulty == 0 || !io::stderr().is_terminal() {
+        return signing.await;
+    }
+
+    tokio::pin!(signing);
+    let mut ticker = tokio::time::interval(Duration::from_millis(500));
+    let event = loop {
+        tokio::select! {
+            result = &mut signing => break result,
+            _ = ticker.tick() => eprint!("\r\x1b[K{}", describe_mining(job)),
+        }
+    };
+    eprint!("\r\x1b[K");
+    event
+}
+
+/// `Mining 20 bits: 1.2M hashes in 3s, ~5s left`
+pub fn describe_mining(job: &MiningJob) -> String {
+    let eta = job.eta().map_or_else(|| String::from("estimating"), |eta| format!("~{}s left", eta.as_secs()));
+    format!(
+        "Mining {} bits: {:.1}M hashes in {}s, {}",
+        job.difficulty,
+        job.attempts() as f64 / 1_000_000.0,
+        job.elapsed().as_secs(),
+        eta,
+    )
+}
+
+fn read_stdin() -> Result<String, NostratuiError> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    Ok(content)
+}
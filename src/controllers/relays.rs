@@ -0,0 +1,42 @@
+use chrono::{Local, TimeZone};
+use crate::models::NostrClient;
+use crate::error::NostratuiError;
+
+/// Probe every relay and print its health, then its recent NOTICE and CLOSED messages
+pub async fn relays_command(client: &NostrClient) -> Result<(), NostratuiError> {
+    let relays = client.probe_relays().await;
+
+    let url_width = relays.iter()
+        .map(|health| health.url.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("RELAY".len());
+    println!("{:<url_width$}  {:<12}  {:>8}  {:>7}  LAST ERROR", "RELAY", "STATE", "LATENCY", "EVENTS");
+    for health in &relays {
+        let latency = health.latency_ms.map_or_else(|| String::from("-"), |ms| format!("{}ms", ms));
+        println!(
+            "{:<url_width$}  {:<12}  {:>8}  {:>7}  {}",
+            health.url,
+            health.state,
+            latency,
+            health.events_received,
+            health.last_error.as_deref().unwrap_or("-"),
+        );
+    }
+
+    for health in relays.iter().filter(|health| !health.messages.is_empty()) {
+        println!("\n{}", health.url);
+        for entry in &health.messages {
+            let at = Local.timestamp_opt(entry.at as i64, 0)
+                .single()
+                .map_or_else(String::new, |time| time.format("%H:%M:%S").to_string());
+            println!("  {} {:<6} {}", at, entry.kind, entry.message);
+        }
+    }
+
+    if relays.iter().any(|health| health.is_connected()) {
+        Ok(())
+    } else {
+        Err(NostratuiError::Network("No relay is connected".to_string()))
+    }
+}
@@ -0,0 +1,49 @@
+use nostr_sdk::prelude::*;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::cli::StreamFormat;
+use crate::controllers::fetch::fill_reposts;
+use crate::models::{NostrClient, Config, Post, ProfileStore, nip18, pow};
+use crate::error::NostratuiError;
+
+pub async fn stream_notes(client: &NostrClient, config: &Config, format: StreamFormat) -> Result<(), NostratuiError> {
+    let profiles = ProfileStore::load(&config.contacts)?;
+    let (subscription_id, mut notifications) = client.subscribe_notes(Timestamp::now()).await?;
+
+    loop {
+        match notifications.recv().await {
+            Ok(RelayPoolNotification::Event { subscription_id: id, event, .. }) => {
+                if id != subscription_id || !matches!(event.kind, Kind::TextNote | Kind::Repost) {
+                    continue;
+                }
+                if pow::difficulty_of(&event.id.to_hex()) < config.min_incoming_pow || !nip18::has_valid_embed(&event) {
+                    continue;
+                }
+                let mut post = Post::from_event(&event);
+                if post.is_repost() && post.repost.is_none() {
+                    if let Err(e) = fill_reposts(client, std::slice::from_mut(&mut post), false).await {
+                        log::warn!("Failed to fetch reposted note: {}", e);
+                    }
+                }
+                println!("{}", format_post(&post, &profiles, format)?);
+            },
+            Ok(RelayPoolNotification::Shutdown) => break,
+            Ok(_) => {},
+            Err(RecvError::Lagged(skipped)) => log::warn!("Stream lagged, skipped {} notifications", skipped),
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    Ok(())
+}
+
+fn format_post(post: &Post, profiles: &ProfileStore, format: StreamFormat) -> Result<String, NostratuiError> {
+    match format {
+        StreamFormat::Text => {
+            // Keep one note per line so the output stays greppable
+            let content = post.content.lines().collect::<Vec<_>>().join(" ");
+            Ok(format!("[{}] {}: {}", post.datetime, profiles.byline(post), content))
+        },
+        StreamFormat::Json => Ok(serde_json::to_string(post)?),
+    }
+}
@@ -27,6 +27,9 @@ pub enum NostratuiError {
 
     #[error("Anyhow error: {0}")]
     Anyhow(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
 }
 
 impl From<Box<dyn std::error::Error>> for NostratuiError {
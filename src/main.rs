@@ -1,9 +1,9 @@
 use nostratui::{
-    cli::Flags,
-    models::{NostrClient, Config, cache::is_cache_empty},
-    controllers::{start_app, init_feed, create_post_via_editor, post_note}
+    cli::{Flags, Command, ContactsCommand, DraftsCommand},
+    models::{NostrClient, Config, FeedQuery, cache::is_cache_empty},
+    controllers::{start_app, init_feed, stream_notes, post_command, repost_command, fetch_command, relays_command, list_drafts, resume_draft, publish_draft, delete_draft, list_contacts, follow_contact, unfollow_contact}
 };
-use nostr_sdk::Timestamp;
+use nostr_sdk::{PublicKey, Timestamp};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -12,24 +12,65 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Load config
     let mut config = Config::load()?;
-    let last_login = config.get_last_login();
+
+    // Drafts are local, listing or deleting them doesn't need relays
+    match &flags.command {
+        Some(Command::Drafts { action: None | Some(DraftsCommand::List) }) => return Ok(list_drafts()?),
+        Some(Command::Drafts { action: Some(DraftsCommand::Delete { draft }) }) => return Ok(delete_draft(draft)?),
+        _ => {},
+    }
 
     // Initialize client and connect relays
     let mut client = NostrClient::new(config.key.clone()).unwrap();
     client.set_relays(config.relays.clone());
-    let _client_connected = client.connect_relays().await?;
+    client.set_fetch_limits(config.fetch_chunk_size, config.fetch_concurrency);
+    client.set_pow(config.pow_settings());
+    // A dry run of a note that doesn't reply to or quote anything never needs a relay
+    let offline = matches!(flags.command, Some(Command::Post { dry_run: true, reply_to: None, quote: None, .. }));
+    client.connect_relays(!offline).await?;
 
-    match true {
-        _ if flags.post() => {
+    match flags.command {
+        Some(Command::Post { content, reply_to, quote, dry_run }) => {
             // Post a new note
-            match create_post_via_editor() {
-                Ok(note) => post_note(&client, note, None).await?,
-                Err(e) => eprintln!("Error creating post: {}", e),
+            post_command(&client, content, reply_to, quote, dry_run).await?
+        },
+        Some(Command::Repost { event, dry_run }) => {
+            repost_command(&client, event, dry_run).await?
+        },
+        Some(Command::Fetch { since, until, authors, limit, format, no_cache }) => {
+            // Fetch, print and optionally cache notes
+            let authors = authors.iter()
+                .map(|author| PublicKey::parse(author))
+                .collect::<Result<Vec<PublicKey>, _>>()?;
+            let query = FeedQuery { since, until, authors, limit };
+            fetch_command(&mut client, &mut config, query, format, no_cache).await?
+        },
+        Some(Command::Stream { format }) => {
+            // Tail new notes from contacts until the relays shut down
+            client.set_contacts(config.contacts.clone()).await?;
+            stream_notes(&client, &config, format).await?
+        },
+        Some(Command::Contacts { action }) => {
+            match action.unwrap_or(ContactsCommand::List) {
+                ContactsCommand::List => list_contacts(&client, &config).await?,
+                ContactsCommand::Add { pubkey, name, relay, init } => {
+                    follow_contact(&client, &mut config, &pubkey, name, relay, init).await?
+                },
+                ContactsCommand::Remove { pubkey, init } => {
+                    unfollow_contact(&client, &mut config, &pubkey, init).await?
+                },
             }
         },
-        _ if flags.fetch() => {
-            // Fetch and update feed
-            init_feed(&mut client, &mut config, last_login).await?
+        Some(Command::Relays) => {
+            // Report relay health, failing when none connected
+            relays_command(&client).await?
+        },
+        Some(Command::Drafts { action }) => {
+            match action.unwrap_or(DraftsCommand::List) {
+                DraftsCommand::Resume { draft } => resume_draft(&client, &draft).await?,
+                DraftsCommand::Publish { draft } => publish_draft(&client, &draft).await?,
+                DraftsCommand::List | DraftsCommand::Delete { .. } => unreachable!("handled before connecting"),
+            }
         },
         _ => {
             // Start TUI application
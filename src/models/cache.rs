@@ -1,11 +1,46 @@
+use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::path::{PathBuf, Path};
+use std::sync::Mutex;
 
+use nostr_sdk::prelude::*;
+
+use crate::models::NostrClient;
 use crate::models::post::Post;
+use crate::models::store::{PostStore, Record, LegacyPost};
 use crate::error::NostratuiError;
 
+// Opened on first use so every caller shares one index
+static STORE: Mutex<Option<PostStore>> = Mutex::new(None);
+// Notes reposted by follows, kept apart so they don't show up in the feed
+static REPOSTED_STORE: Mutex<Option<PostStore>> = Mutex::new(None);
+
 pub fn get_cache_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("posts.log"))
+}
+
+/// Notes that reposts didn't embed, fetched separately
+pub fn get_reposted_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("reposted.log"))
+}
+
+/// The pretty-printed JSON array used before the post store existed
+pub fn get_legacy_cache_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("posts.json"))
+}
+
+/// Where drafts were kept before they moved to the data directory
+pub fn get_legacy_drafts_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("drafts.json"))
+}
+
+/// Ids of legacy posts the relays didn't have, so they aren't requested on every launch
+pub fn get_missing_legacy_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("legacy_missing.json"))
+}
+
+pub fn get_cache_dir() -> Result<PathBuf, NostratuiError> {
     // Check the XDG_CACHE_HOME environment variable first
     let base_cache_dir = env::var_os("XDG_CACHE_HOME")
         .map(PathBuf::from)
@@ -23,50 +58,158 @@ pub fn get_cache_file() -> Result<PathBuf, NostratuiError> {
     fs::create_dir_all(&app_cache_dir)
         .map_err(|e| NostratuiError::Cache(format!("Failed to create cache directory: {}",e)))?;
 
-    Ok(app_cache_dir.join("posts.json"))
+    Ok(app_cache_dir)
 }
 
-pub fn load_cached_posts() -> Result<Vec<Post>, NostratuiError> {
-    let cache_path = get_cache_file()?;
-    if !cache_path.exists() {
-        return Ok(Vec::new());
+fn with_store<T, F>(f: F) -> Result<T, NostratuiError>
+where
+    F: FnOnce(&mut PostStore) -> Result<T, NostratuiError>,
+{
+    let open = || {
+        let mut store = PostStore::open(&get_cache_file()?)?;
+        migrate_legacy_cache(&mut store)?;
+        Ok(store)
+    };
+    with_opened(&STORE, open, f)
+}
+
+fn with_reposted_store<T, F>(f: F) -> Result<T, NostratuiError>
+where
+    F: FnOnce(&mut PostStore) -> Result<T, NostratuiError>,
+{
+    with_opened(&REPOSTED_STORE, || PostStore::open(&get_reposted_file()?), f)
+}
+
+fn with_opened<T, O, F>(slot: &Mutex<Option<PostStore>>, open: O, f: F) -> Result<T, NostratuiError>
+where
+    O: FnOnce() -> Result<PostStore, NostratuiError>,
+    F: FnOnce(&mut PostStore) -> Result<T, NostratuiError>,
+{
+    let mut guard = slot.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if guard.is_none() {
+        *guard = Some(open()?);
     }
 
-    let cache_data = fs::read_to_string(cache_path)?;
-    let mut posts: Vec<Post> = serde_json::from_str(&cache_data)?;
-    
-    // Ensure all posts have a mentions field, defaulting to empty if missing
-    for post in &mut posts {
-        if post.mentions.is_empty() {
-            post.mentions = Vec::new();
-        }
+    f(guard.as_mut().expect("store was opened above"))
+}
+
+// Import posts.json once, then move it aside so it isn't imported again
+fn migrate_legacy_cache(store: &mut PostStore) -> Result<(), NostratuiError> {
+    let legacy_path = get_legacy_cache_file()?;
+    if !legacy_path.exists() {
+        return Ok(());
     }
-    
-    Ok(posts)
+
+    let cache_data = fs::read_to_string(&legacy_path)?;
+    let posts: Vec<LegacyPost> = serde_json::from_str(&cache_data)?;
+    let imported = store.insert_legacy(posts)?;
+
+    fs::rename(&legacy_path, legacy_path.with_extension("json.migrated"))
+        .map_err(|e| NostratuiError::Cache(format!("Failed to move legacy cache aside: {}", e)))?;
+    log::info!("Migrated {} posts from {:?}", imported, legacy_path);
+    Ok(())
 }
 
-pub fn save_posts_to_cache(new_posts: Vec<Post>) -> Result<(), NostratuiError> {
-    let mut cached_posts = load_cached_posts()?;
+/// Replace posts cached without their signed event by refetching the originals.
+///
+/// Events the relays no longer have are left as they are and remembered,
+/// so each legacy id is only ever asked for once.
+pub async fn upgrade_legacy_records(client: &NostrClient) -> Result<usize, NostratuiError> {
+    let mut missing = load_missing_legacy()?;
+    let ids: Vec<EventId> = with_store(|store| Ok(store.legacy_ids()))?
+        .iter()
+        .filter(|id| !missing.contains(*id))
+        .filter_map(|id| EventId::from_hex(id).ok())
+        .collect();
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let events = client.fetch_events_by_id(&ids).await?;
+    let found: HashSet<EventId> = events.iter().map(|event| event.id).collect();
+    let upgraded = with_store(|store| store.insert_events(events))?;
 
-    for post in new_posts {
-        if !cached_posts.iter().any(|p| p.id == post.id) {
-            cached_posts.push(post);
-        }
+    missing.extend(ids.iter().filter(|id| !found.contains(id)).map(EventId::to_hex));
+    fs::write(get_missing_legacy_file()?, serde_json::to_string(&missing)?)?;
+    Ok(upgraded)
+}
+
+fn load_missing_legacy() -> Result<HashSet<String>, NostratuiError> {
+    let path = get_missing_legacy_file()?;
+    if !path.exists() {
+        return Ok(HashSet::new());
     }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
 
-    cached_posts.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
+/// All cached posts, newest first
+pub fn load_cached_posts() -> Result<Vec<Post>, NostratuiError> {
+    with_store(|store| to_posts(store.all()))
+}
 
-    let cache_path = get_cache_file()?;
-    let json = serde_json::to_string_pretty(&cached_posts)
-        .map_err(|e| NostratuiError::Cache(format!("Failed to serialize posts: {}", e)))?;
-    
-    fs::write(cache_path, json)
-        .map_err(|e| NostratuiError::Cache(format!("Failed to write cache file: {}", e)))?;
-    
+pub fn save_events_to_cache(new_events: Vec<Event>) -> Result<(), NostratuiError> {
+    with_store(|store| store.insert_events(new_events))?;
     Ok(())
 }
 
+/// Keep the notes fetched for reposts that didn't embed them, so they aren't fetched again
+pub fn save_reposted_events(events: Vec<Event>) -> Result<(), NostratuiError> {
+    let notes: Vec<Event> = events.into_iter().filter(|event| event.kind == Kind::TextNote).collect();
+    with_reposted_store(|store| store.insert_events(notes))?;
+    Ok(())
+}
+
+/// A cached event by hex id, from the feed or the notes kept for reposts
+pub fn get_cached_event(id: &str) -> Result<Option<Event>, NostratuiError> {
+    match with_store(|store| store.get(id))? {
+        Some(Record::Event(event)) => Ok(Some(event)),
+        _ => match with_reposted_store(|store| store.get(id))? {
+            Some(Record::Event(event)) => Ok(Some(event)),
+            _ => Ok(None),
+        },
+    }
+}
+
+/// Fill in the reposted notes kept from earlier fetches
+pub fn fill_cached_reposts(posts: &mut [Post]) -> Result<(), NostratuiError> {
+    with_reposted_store(|store| {
+        for post in posts.iter_mut().filter(|post| post.repost.is_none()) {
+            let Some(reposted_id) = post.reposted_id.clone() else {
+                continue;
+            };
+            if let Some(Record::Event(original)) = store.get(&reposted_id)? {
+                post.set_repost(Post::from_event(&original));
+            }
+        }
+        Ok(())
+    })
+}
+
+pub fn get_cached_post(id: &str) -> Result<Option<Post>, NostratuiError> {
+    Ok(with_store(|store| store.get(id))?.map(|record| record.to_post()))
+}
+
+/// Cached posts in an inclusive timestamp window, newest first
+pub fn load_posts_between(since: Option<u64>, until: Option<u64>) -> Result<Vec<Post>, NostratuiError> {
+    with_store(|store| to_posts(store.range(since, until)))
+}
+
+/// Cached posts by one hex author key in an inclusive timestamp window, newest first
+pub fn load_posts_by_author(author: &str, since: Option<u64>, until: Option<u64>) -> Result<Vec<Post>, NostratuiError> {
+    with_store(|store| to_posts(store.range_by_author(author, since, until)))
+}
+
 pub fn is_cache_empty() -> Result<bool,NostratuiError> {
-    let cache_files = load_cached_posts()?;
-    Ok(cache_files.is_empty())
+    with_store(|store| Ok(store.is_empty()))
+}
+
+fn to_posts(records: Result<Vec<Record>, NostratuiError>) -> Result<Vec<Post>, NostratuiError> {
+    let mut posts: Vec<Post> = records?.iter().map(Record::to_post).collect();
+    // Reposts still show without their notes, which are fetched again
+    if let Err(e) = fill_cached_reposts(&mut posts) {
+        log::warn!("Failed to read reposted notes: {}", e);
+    }
+    Ok(posts)
 }
@@ -1,10 +1,42 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use chrono::{DateTime, Local, Utc, TimeZone};
+use futures::StreamExt;
 use nostr_sdk::prelude::*;
 use crate::models::post::Post;
+use crate::models::nip10::ReplyTo;
+use crate::models::profile::{Profile, ProfileStore};
+use crate::models::sync::SyncState;
+use crate::models::relay::{RelayHealth, RelayMonitor};
+use crate::models::pow::{self, MiningJob, PowSettings};
+use crate::models::{nip18, notification, reaction};
 use crate::error::NostratuiError;
 use anyhow::{Context, Result};
 use tokio::time::timeout;
+use tokio::sync::broadcast;
+
+/// Authors per metadata filter, relays commonly cap filters at a few hundred keys
+const PROFILE_CHUNK_SIZE: usize = 250;
+pub const DEFAULT_FETCH_CHUNK_SIZE: usize = 100;
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 4;
+/// Bound on ancestor hops and descendant rounds when loading a thread
+const THREAD_MAX_DEPTH: usize = 32;
+/// Event ids per filter when loading a thread
+const THREAD_ID_CHUNK_SIZE: usize = 250;
+/// Time a whole thread load may take, rounds still pending after it are skipped
+const THREAD_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest wait for a single round of a thread load
+const THREAD_ROUND_TIMEOUT: Duration = Duration::from_secs(15);
+/// Reactions asked for per chunk of notes
+const REACTION_FETCH_LIMIT: usize = 2000;
+/// Kinds that make up the feed: notes and reposts of them
+const FEED_KINDS: [Kind; 2] = [Kind::TextNote, Kind::Repost];
+/// How long to wait for relays to connect before carrying on without them
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Events asked for per sync request, a full page means the relay may have more
+const SYNC_PAGE_LIMIT: usize = 500;
+/// Pages fetched back in time per sync request before the rest is left for the next sync
+const SYNC_MAX_PAGES: usize = 10;
 
 #[derive(Clone)]
 pub struct NostrClient {
@@ -12,7 +44,13 @@ pub struct NostrClient {
     client: Client,
     key: Keys,
     contacts: Vec<Contact>,
-    relays: Vec<String>
+    relays: Vec<String>,
+    fetch_chunk_size: usize,
+    fetch_concurrency: usize,
+    relay_monitor: Arc<RelayMonitor>,
+    pow: PowSettings,
+    /// Difficulty relays asked for this session, in their NIP-11 document or when rejecting our notes
+    learned_pow: Arc<Mutex<HashMap<String, u8>>>,
 }
 
 
@@ -22,7 +60,71 @@ pub struct Contact {
     name: String,
 }
 
+/// Time window and authors for a feed fetch, unset fields are unbounded
+#[derive(Clone, Debug, Default)]
+pub struct FeedQuery {
+    pub since: Option<Timestamp>,
+    pub until: Option<Timestamp>,
+    /// Authors to fetch, all contacts when empty
+    pub authors: Vec<PublicKey>,
+    pub limit: Option<usize>,
+}
+
+/// Outcome of a feed fetch, events are deduplicated across relays
+#[derive(Clone, Debug, Default)]
+pub struct FeedFetch {
+    pub events: Vec<Event>,
+    pub relays: Vec<RelayFetchSummary>,
+}
+
+/// What a single relay contributed to a feed fetch
+#[derive(Clone, Debug, Default)]
+pub struct RelayFetchSummary {
+    pub relay: String,
+    pub requests: usize,
+    /// Events returned, including ones other relays also returned
+    pub events: usize,
+    pub errors: Vec<String>,
+}
+
+/// How each relay answered a published event
+#[derive(Debug, Clone)]
+pub struct PublishResult {
+    pub event_id: EventId,
+    pub accepted: Vec<String>,
+    /// Relay and its reason, from the OK message or NOTICEs it sent meanwhile
+    pub rejected: Vec<(String, String)>,
+}
+
+impl FeedFetch {
+    /// Whether every request to every relay failed, as opposed to relays having nothing to return
+    pub fn all_failed(&self) -> bool {
+        let requests: usize = self.relays.iter().map(|summary| summary.requests).sum();
+        requests > 0 && self.relays.iter().all(|summary| summary.errors.len() == summary.requests)
+    }
+}
+
+impl FeedQuery {
+    fn filter(&self) -> Filter {
+        let mut filter = Filter::new().kinds(FEED_KINDS);
+        if let Some(since) = self.since {
+            filter = filter.since(since);
+        }
+        if let Some(until) = self.until {
+            filter = filter.until(until);
+        }
+        if let Some(limit) = self.limit {
+            filter = filter.limit(limit);
+        }
+        filter
+    }
+}
+
 impl Contact {
+    pub fn public_key(&self) -> PublicKey {
+        self.key
+    }
+
     pub fn to_string_tuple(&self) -> (String,String) {
         (self.key.to_bech32().unwrap(),self.name.clone())
     }
@@ -38,11 +140,16 @@ impl NostrClient {
             key,
             contacts: vec![],
             relays: vec![],
+            fetch_chunk_size: DEFAULT_FETCH_CHUNK_SIZE,
+            fetch_concurrency: DEFAULT_FETCH_CONCURRENCY,
+            relay_monitor: Arc::new(RelayMonitor::default()),
+            pow: PowSettings::default(),
+            learned_pow: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
     pub fn get_contacts(&self) -> Vec<Contact> {
-        return self.contacts.clone()
+        self.contacts.clone()
     }
 
     pub fn print(&self) -> Result<()> {
@@ -61,11 +168,57 @@ impl NostrClient {
         self.relays = relays;
     }
 
+    /// Authors per feed filter and how many feed requests may be in flight at once
+    pub fn set_fetch_limits(&mut self, chunk_size: usize, concurrency: usize) {
+        self.fetch_chunk_size = chunk_size;
+        self.fetch_concurrency = concurrency;
+    }
+
+    /// Per-relay difficulties are keyed without a trailing `/`, like the ones learned from relays
+    pub fn set_pow(&mut self, mut pow: PowSettings) {
+        pow.relay_difficulty = pow.relay_difficulty.into_iter()
+            .map(|(relay, difficulty)| (relay.trim_end_matches('/').to_string(), difficulty))
+            .collect();
+        self.pow = pow;
+    }
+
+    /// Difficulty to mine notes to: the highest of the global setting and what any of our relays wants
+    pub fn pow_difficulty(&self) -> u8 {
+        self.relay_pow_difficulty().max(self.pow.difficulty)
+    }
+
+    /// The most proof of work any of our relays wants, configured or learned from them
+    fn relay_pow_difficulty(&self) -> u8 {
+        let learned = self.learned_pow.lock().unwrap_or_else(|e| e.into_inner());
+        self.relays.iter()
+            .filter_map(|relay| {
+                let relay = relay.trim_end_matches('/');
+                let configured = self.pow.relay_difficulty.get(relay).copied();
+                let learned = learned.get(relay).copied();
+                configured.max(learned)
+            })
+            .fold(0, u8::max)
+    }
+
+    /// A fresh mining job at the current difficulty, to pass to [`Self::sign_note`]
+    pub fn mining_job(&self) -> Arc<MiningJob> {
+        MiningJob::new(self.pow_difficulty())
+    }
+
+    /// A mining job for reactions and reposts: the global difficulty is for notes,
+    /// so these only get the work a relay asks for
+    pub fn light_mining_job(&self) -> Arc<MiningJob> {
+        MiningJob::new(self.relay_pow_difficulty())
+    }
+
     pub fn get_relays(&self) -> Vec<String> {
-        return self.relays.clone()
+        self.relays.clone()
     }
 
-    pub async fn connect_relays(&mut self) -> Result<(),NostratuiError> {
+    /// Add and connect the relays, waiting up to [`CONNECT_TIMEOUT`] for them when `wait` is set.
+    ///
+    /// Commands that never touch the network pass `false` so they don't pay for the wait.
+    pub async fn connect_relays(&mut self, wait: bool) -> Result<(),NostratuiError> {
         let mut connection_results = Vec::new();
 
         for relay in &self.relays {
@@ -77,80 +230,151 @@ impl NostrClient {
             }
         }
 
+        self.spawn_relay_monitor();
         self.client.connect().await;
 
+        // Relays keep reconnecting in the background, note the ones that aren't up yet
+        for (relay, result) in self.relays.iter().zip(&connection_results) {
+            if let Err(e) = result {
+                self.relay_monitor.record_error(relay, e.to_string());
+            }
+        }
+        if wait {
+            tokio::join!(
+                self.client.wait_for_connection(CONNECT_TIMEOUT),
+                self.learn_advertised_pow(),
+            );
+            for health in self.relay_health().await {
+                if !health.is_connected() && health.last_error.is_none() {
+                    log::warn!("Relay {} not connected after {}s", health.url, CONNECT_TIMEOUT.as_secs());
+                    self.relay_monitor.record_error(&health.url, format!("Not connected after {}s", CONNECT_TIMEOUT.as_secs()));
+                }
+            }
+        }
+
         if connection_results.iter().any(Result::is_ok) {
             Ok(())
         } else {
-            Err(NostratuiError::Network("Failed to connect to any relays".to_string()).into())
+            Err(NostratuiError::Network("Failed to connect to any relays".to_string()))
         }
         
     }
 
+    /// Remember the `min_pow_difficulty` relays advertise in their NIP-11 document, when matching relay minimums
+    async fn learn_advertised_pow(&self) {
+        if !self.pow.match_relay {
+            return;
+        }
+        let documents = self.relays.iter().map(|relay| async move {
+            let url = Url::parse(relay).ok()?;
+            let document = timeout(CONNECT_TIMEOUT, RelayInformationDocument::get(url, None)).await.ok()?.ok()?;
+            let required = document.limitation?.min_pow_difficulty?;
+            Some((relay.trim_end_matches('/').to_string(), required.clamp(0, u8::MAX as i32) as u8))
+        });
+        let advertised = futures::future::join_all(documents).await;
+
+        let mut learned = self.learned_pow.lock().unwrap_or_else(|e| e.into_inner());
+        for (relay, required) in advertised.into_iter().flatten() {
+            log::info!("{} asks for {} bits of proof of work", relay, required);
+            let known = learned.entry(relay).or_insert(0);
+            *known = (*known).max(required);
+        }
+    }
+
+    // Count events and collect NOTICE/CLOSED messages for every relay
+    fn spawn_relay_monitor(&self) {
+        let mut notifications = self.client.notifications();
+        let monitor = Arc::clone(&self.relay_monitor);
+        tokio::spawn(async move {
+            loop {
+                match notifications.recv().await {
+                    Ok(notification) => monitor.record_notification(&notification),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Connection state, latency, errors and messages for each configured relay
+    pub async fn relay_health(&self) -> Vec<RelayHealth> {
+        let pool = self.client.relays().await;
+        self.relay_monitor.snapshot(&self.relays, &pool)
+    }
+
+    /// Time a small request to each relay, so latency is known even before the pool pings
+    pub async fn probe_relays(&self) -> Vec<RelayHealth> {
+        let filter = Filter::new().author(self.my_key()).kind(Kind::Metadata).limit(1);
+        let probes = self.relays.iter().map(|relay| {
+            let filter = filter.clone();
+            async move {
+                let started = std::time::Instant::now();
+                let result = self.client.fetch_events_from([relay.as_str()], filter, Duration::from_secs(10)).await;
+                match result {
+                    Ok(_) => self.relay_monitor.record_latency(relay, started.elapsed()),
+                    Err(e) => self.relay_monitor.record_error(relay, e.to_string()),
+                }
+            }
+        });
+        futures::future::join_all(probes).await;
+        self.relay_health().await
+    }
+
     //This will get who the user is following
     pub async fn fetch_contacts(&mut self) -> Result<Vec<Contact>> {
-        let my_pub_key = self.key.public_key();
-        let filter = Filter::new().author(my_pub_key).kind(Kind::ContactList);
-        
-        // Use timeout for fetching events
-        let my_contacts_note = timeout(
-            Duration::from_secs(15),
-            self.client.fetch_events(filter, Duration::from_secs(10))
-        )
-        .await
-        .context("Timeout fetching contact list")?
-        .context("Failed to fetch contact list")?;
-        
-        let mut contacts = vec![];
-        
-        if let Some(fetched_contact_list) = my_contacts_note.first() {
-            let tags = &fetched_contact_list.tags;
-            
-            
-            for tag in tags.iter() {
-                if let Some(following) = tag.content() {
-                    let following_pk = match PublicKey::from_hex(following) {
-                        Ok(pk) => pk,
-                        Err(_) => continue,
-                    };
-                    
-                    let metadata_filter = Filter::new()
-                        .author(following_pk)
-                        .kind(Kind::Metadata);
-                            
-                    let metadata_result = self.client.fetch_events(metadata_filter, Duration::from_secs(10)).await;
-                        
-                    match metadata_result {
-                        Ok(fetched_contact_metadata) => {
-                            if let Some(metadata) = fetched_contact_metadata.first() {
-                                if let Ok(value) = serde_json::from_str::<serde_json::Value>(&metadata.content) {
-                                    if let Some(name) = value.get("name").and_then(|n| n.as_str()) {
-                                        println!("found metadata for name!");
-                                        contacts.push(
-                                            Contact {
-                                                key: following_pk,
-                                                name: name.to_string(),
-                                            });
-                                    }                                 }
-                            } else {
-                                println!("Needed fallback");
-                                // Fallback to using pubkey as name
-                                contacts.push(
-                                    Contact {
-                                        key: following_pk,
-                                        name: following_pk.to_bech32().unwrap_or_default(),
-                                    });
-                            }
-                        },
-                        _ => {}
+        let contact_list = timeout(Duration::from_secs(15), self.fetch_contact_list())
+            .await
+            .context("Timeout fetching contact list")?
+            .context("Failed to fetch contact list")?;
 
-                    }
+        // Petnames from the contact list win over whatever the contact calls themselves
+        let mut followed: Vec<(PublicKey, Option<String>)> = Vec::new();
+        if let Some(contact_list) = contact_list {
+            for tag in contact_list.tags.iter() {
+                let values = tag.as_slice();
+                if values.first().map(String::as_str) != Some("p") {
+                    continue;
+                }
+                if let Some(public_key) = values.get(1).and_then(|key| PublicKey::from_hex(key).ok()) {
+                    let petname = values.get(3).filter(|name| !name.is_empty()).cloned();
+                    followed.push((public_key, petname));
                 }
             }
         }
-            
-        
-        Ok(contacts)
+
+        let mut profiles = ProfileStore::load(&[])?;
+        let keys: Vec<PublicKey> = followed.iter().map(|(pk, _)| *pk).collect();
+        match self.fetch_profiles(keys).await {
+            Ok(fetched) => {
+                profiles.merge(fetched);
+                profiles.save()?;
+            },
+            Err(e) => log::warn!("Failed to fetch contact profiles: {}", e),
+        }
+
+        Ok(followed.into_iter()
+            .map(|(key, petname)| Contact {
+                key,
+                name: petname.unwrap_or_else(|| profiles.display_name_for(&key)),
+            })
+            .collect())
+    }
+
+    /// Fetch the newest kind 3 contact list published by this key, if any
+    pub async fn fetch_contact_list(&self) -> Result<Option<Event>, NostratuiError> {
+        let filter = Filter::new().author(self.my_key()).kind(Kind::ContactList);
+        let events = self.client.fetch_events(filter, Duration::from_secs(10)).await?;
+        Ok(events.into_iter().max_by_key(|event| event.created_at))
+    }
+
+    /// Replace the published contact list with one carrying `tags` and `content`
+    pub async fn publish_contact_list(&self, tags: Vec<Tag>, content: String) -> Result<EventId, NostratuiError> {
+        let builder = EventBuilder::new(Kind::ContactList, content).tags(tags);
+        let output = self.client.send_event_builder(builder).await?;
+        if output.success.is_empty() {
+            return Err(NostratuiError::Network("No relay accepted the contact list".to_string()));
+        }
+        Ok(output.val)
     }
 
     pub async fn set_contacts(&mut self, contacts: Vec<(String,String)>) -> Result<()> {
@@ -172,203 +396,466 @@ impl NostrClient {
         Ok(())
     }
 
-    pub async fn fetch_notes_since(&self, timestamp: Timestamp) -> Result<Vec<Post>, NostratuiError> {
-        let mut new_posts: Vec<Post> = vec![];
-        let mut tasks = Vec::new();
-        
-        for contact in self.contacts.clone() {
-            let client_clone = self.client.clone();
-            let pub_key = contact.key;
-            let user = contact.name.clone();
-            
-            tasks.push(tokio::spawn(async move {
+    pub async fn fetch_notes_since(&self, timestamp: Timestamp) -> Result<FeedFetch, NostratuiError> {
+        let query = FeedQuery {
+            since: Some(timestamp),
+            ..FeedQuery::default()
+        };
+        self.fetch_notes(&query).await
+    }
+
+    /// Fetch the signed note events matching `query`, newest first.
+    ///
+    /// Authors are split into chunks of `fetch_chunk_size` and each chunk is
+    /// requested from every relay separately, at most `fetch_concurrency` at a
+    /// time, so relays see a handful of REQs instead of one per contact.
+    pub async fn fetch_notes(&self, query: &FeedQuery) -> Result<FeedFetch, NostratuiError> {
+        let authors: Vec<PublicKey> = if query.authors.is_empty() {
+            self.contact_keys()
+        } else {
+            query.authors.clone()
+        };
+
+        let mut requests = Vec::new();
+        for chunk in authors.chunks(self.fetch_chunk_size.max(1)) {
+            let filter = query.filter().authors(chunk.iter().copied());
+            for relay in &self.relays {
+                requests.push(FeedRequest { relay: relay.clone(), authors: chunk.to_vec(), filter: filter.clone() });
+            }
+        }
+
+        let results = self.run_feed_requests(requests).await;
+        let mut fetched = merge_feed_results(&self.relays, &results);
+        if let Some(limit) = query.limit {
+            fetched.events.truncate(limit);
+        }
+        Ok(fetched)
+    }
+
+    /// Fetch new notes from all contacts, starting each relay where its cursors left off.
+    ///
+    /// Authors without a cursor on a relay start at `default_since`. Cursors
+    /// only advance for requests that succeeded, so a relay that was down is
+    /// backfilled from where it stopped on the next sync. A relay that returns
+    /// a full page is paged back in time, and if it still has more after
+    /// [`SYNC_MAX_PAGES`] its cursors stay put so the rest is fetched next time.
+    pub async fn sync_notes(&self, sync: &mut SyncState, default_since: Timestamp) -> Result<FeedFetch, NostratuiError> {
+        let started = Timestamp::now();
+        let authors = self.contact_keys();
+
+        let mut requests = Vec::new();
+        for chunk in authors.chunks(self.fetch_chunk_size.max(1)) {
+            for relay in &self.relays {
                 let filter = Filter::new()
-                    .author(pub_key)
-                    .kind(Kind::TextNote)
-                    .since(timestamp);
-                    
-                let result = tokio::time::timeout(
-                    Duration::from_secs(10),
-                    client_clone.fetch_events(filter, Duration::from_secs(30))
-                ).await;
-                
-                match result {
-                    Ok(Ok(events)) => {
-                        let mut contact_posts = Vec::new();
-                        for event in events {
-                            let utc_time = Utc.timestamp_opt(event.created_at.as_u64() as i64, 0).unwrap();
-                            let local_time: DateTime<Local> = DateTime::from(utc_time);
-                            let datetime = local_time.format("%H:%M %h-%d-%Y").to_string();
-                            
-                            // Extract thread information from tags
-                            let mut root_id = None;
-                            let mut reply_id = None;
-                            let mut mentions = Vec::new();
-                            let mut participants = Vec::new();
-
-                            // Process tags for thread information
-                            for tag in event.tags.iter() {
-                                let tag = (*tag).clone();
-                                let vec = tag.to_vec();
-                                if vec.len() >= 2 {
-                                    match vec[0].as_str() {
-                                        "e" => {
-                                            let event_id = vec[1].clone();
-                                            if vec.len() >= 4 {
-                                                match vec[3].as_str() {
-                                                    "root" => root_id = Some(event_id),
-                                                    "reply" => reply_id = Some(event_id),
-                                                    _ => mentions.push(event_id),
-                                                }
-                                            } else {
-                                                // Handle deprecated positional e tags
-                                                if vec.len() == 2 {
-                                                    reply_id = Some(event_id);
-                                                } else if vec.len() == 3 {
-                                                    root_id = Some(event_id);
-                                                }
-                                            }
-                                        }
-                                        "p" => {
-                                            participants.push(vec[1].clone());
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            }
-                            
-                            contact_posts.push(Post {
-                                user: user.clone(),
-                                timestamp: event.created_at.as_u64(),
-                                datetime,
-                                content: event.content.to_string(),
-                                id: event.id.to_hex(),
-                                root_id,
-                                reply_id,
-                                mentions,
-                                participants,
-                            });
-                        }
-                        Ok(contact_posts)
-                    }
-                    Ok(Err(e)) => Err(NostratuiError::NostrSdk(e.to_string())),
-                    Err(_) => Err(NostratuiError::NostrSdk("Timeout fetching events".to_string())),
-                }
-            }));
+                    .kinds(FEED_KINDS)
+                    .authors(chunk.iter().copied())
+                    .since(sync.since_for(relay, chunk, default_since))
+                    .limit(SYNC_PAGE_LIMIT);
+                requests.push(FeedRequest { relay: relay.clone(), authors: chunk.to_vec(), filter });
+            }
         }
-        
-        for task in futures::future::join_all(tasks).await {
-            match task {
-                Ok(Ok(posts)) => new_posts.extend(posts),
-                Ok(Err(e)) => log::warn!("Error fetching posts: {}", e),
-                Err(e) => log::warn!("Task error: {}", e),
+
+        let mut results = self.run_feed_requests(requests).await;
+        for (request, result) in results.iter_mut() {
+            let Ok(events) = result else {
+                continue;
+            };
+            let complete = match self.page_back(request, events).await {
+                Ok(complete) => complete,
+                Err(e) => {
+                    self.relay_monitor.record_error(&request.relay, e.clone());
+                    false
+                },
+            };
+            let until = complete.then_some(started);
+            sync.record(&request.relay, &request.authors, until, events);
+        }
+        Ok(merge_feed_results(&self.relays, &results))
+    }
+
+    // Keep asking for older pages while the relay fills them, returning whether it ran out
+    async fn page_back(&self, request: &FeedRequest, events: &mut Vec<Event>) -> Result<bool, String> {
+        let mut page_len = events.len();
+        let mut seen: HashSet<EventId> = events.iter().map(|event| event.id).collect();
+        for _ in 0..SYNC_MAX_PAGES {
+            if page_len < SYNC_PAGE_LIMIT {
+                return Ok(true);
+            }
+            let Some(oldest) = events.iter().map(|event| event.created_at).min() else {
+                return Ok(true);
+            };
+            // Events sharing the oldest second may have been cut off, so the boundary is asked for again
+            let filter = request.filter.clone().until(oldest);
+            let page = self.client
+                .fetch_events_from([request.relay.as_str()], filter, Duration::from_secs(30))
+                .await
+                .map_err(|e| e.to_string())?;
+            page_len = page.len();
+            let before = events.len();
+            events.extend(page.into_iter().filter(|event| seen.insert(event.id)));
+            if events.len() == before {
+                // A full page of one second's events, nothing older can be reached this way
+                return Ok(false);
             }
         }
-        
-        Ok(new_posts)
+        Ok(page_len < SYNC_PAGE_LIMIT)
     }
 
-    pub async fn post_note(&self, note: String, reply_to: Option<(String, String)>) -> Result<(),NostratuiError> {
-        let mut builder = EventBuilder::text_note(note).pow(20);
-        
-        // If this is a reply, add the appropriate e tags
-        if let Some((root_id, reply_id)) = reply_to {
-            // Add root tag
-            let root_tag = Tag::parse(["e", &root_id, "", "root"])
-                .map_err(|e| NostratuiError::NostrSdk(e.to_string()))?;
-            builder = builder.tag(root_tag);
-            
-            // Add reply tag if it's different from root
-            if reply_id != root_id {
-                let reply_tag = Tag::parse(["e", &reply_id, "", "reply"])
-                    .map_err(|e| NostratuiError::NostrSdk(e.to_string()))?;
-                builder = builder.tag(reply_tag);
+    pub fn contact_keys(&self) -> Vec<PublicKey> {
+        self.contacts.iter().map(|c| c.key).collect()
+    }
+
+    async fn run_feed_requests(&self, requests: Vec<FeedRequest>) -> Vec<(FeedRequest, Result<Vec<Event>, String>)> {
+        futures::stream::iter(requests)
+            .map(|request| async move {
+                let result = self.client
+                    .fetch_events_from([request.relay.as_str()], request.filter.clone(), Duration::from_secs(30))
+                    .await
+                    .map(|events| events.into_iter().collect::<Vec<Event>>())
+                    .map_err(|e| e.to_string());
+                if let Err(e) = &result {
+                    self.relay_monitor.record_error(&request.relay, e.clone());
+                }
+                (request, result)
+            })
+            .buffer_unordered(self.fetch_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Fetch events by id, in chunks so a long list doesn't produce an oversized REQ
+    pub async fn fetch_events_by_id(&self, ids: &[EventId]) -> Result<Vec<Event>, NostratuiError> {
+        let mut events = Vec::new();
+        for chunk in ids.chunks(250) {
+            let filter = Filter::new().ids(chunk.iter().copied());
+            events.extend(self.client.fetch_events(filter, Duration::from_secs(10)).await?);
+        }
+        Ok(events)
+    }
+
+    /// Fetch metadata for many authors with one filter per chunk, keeping the newest event of each.
+    ///
+    /// Every requested author is marked as checked, so ones without metadata
+    /// aren't requested again until their entry goes stale.
+    pub async fn fetch_profiles(&self, authors: Vec<PublicKey>) -> Result<ProfileStore, NostratuiError> {
+        let mut profiles = ProfileStore::default();
+        for chunk in authors.chunks(PROFILE_CHUNK_SIZE) {
+            let filter = Filter::new().authors(chunk.iter().copied()).kind(Kind::Metadata);
+            let events = self.client.fetch_events(filter, Duration::from_secs(10)).await?;
+            for event in events.iter() {
+                if let Some(profile) = Profile::from_event(event) {
+                    profiles.insert(event.pubkey, profile);
+                }
             }
+            profiles.mark_checked(chunk, Timestamp::now().as_u64());
         }
-            
-        self.client.send_event_builder(builder).await?;
-        Ok(())
+        Ok(profiles)
     }
 
-    pub async fn fetch_thread(&self, root_id: &str) -> Result<Vec<Post>, NostratuiError> {
-        let root_event_id = EventId::from_hex(root_id)
-            .map_err(|e| NostratuiError::NostrSdk(e.to_string()))?;
+    /// Open a long-lived subscription for new text notes and reposts from all contacts.
+    ///
+    /// The notification receiver is created before the REQ is sent, so no event
+    /// delivered for the subscription can be missed.
+    pub async fn subscribe_notes(&self, since: Timestamp) -> Result<(SubscriptionId, broadcast::Receiver<RelayPoolNotification>), NostratuiError> {
+        let notifications = self.client.notifications();
 
-        // Create a filter to get all posts in the thread
         let filter = Filter::new()
-            .kind(Kind::TextNote)
-            .event(root_event_id);
+            .authors(self.contacts.iter().map(|c| c.key))
+            .kinds(FEED_KINDS)
+            .since(since);
 
-        let events = self.client.fetch_events(filter, Duration::from_secs(30)).await?;
-        
-        // Convert events to posts and sort by timestamp
-        let mut posts: Vec<Post> = events.into_iter()
-            .map(|event| {
-                let root_id = event.tags.iter()
-                    .find(|t| {
-                        let tag = (*t).clone();
-                        let vec = tag.to_vec();
-                        vec.get(0) == Some(&"e".to_string()) && vec.get(3) == Some(&"root".to_string())
-                    })
-                    .and_then(|t| {
-                        let tag = (*t).clone();
-                        tag.to_vec().get(1).cloned()
-                    });
-
-                let reply_id = event.tags.iter()
-                    .find(|t| {
-                        let tag = (*t).clone();
-                        let vec = tag.to_vec();
-                        vec.get(0) == Some(&"e".to_string()) && vec.get(3) == Some(&"reply".to_string())
-                    })
-                    .and_then(|t| {
-                        let tag = (*t).clone();
-                        tag.to_vec().get(1).cloned()
-                    });
-
-                let mentions = event.tags.iter()
-                    .filter(|t| {
-                        let tag = (*t).clone();
-                        let vec = tag.to_vec();
-                        vec.get(0) == Some(&"e".to_string()) && 
-                        vec.get(3) != Some(&"root".to_string()) && 
-                        vec.get(3) != Some(&"reply".to_string())
-                    })
-                    .filter_map(|t| {
-                        let tag = (*t).clone();
-                        tag.to_vec().get(1).cloned()
-                    })
-                    .collect();
+        let output = self.client.subscribe(filter, None).await?;
+        Ok((output.val, notifications))
+    }
 
-                let participants = event.tags.iter()
-                    .filter(|t| {
-                        let tag = (*t).clone();
-                        let vec = tag.to_vec();
-                        vec.get(0) == Some(&"p".to_string())
-                    })
-                    .filter_map(|t| {
-                        let tag = (*t).clone();
-                        tag.to_vec().get(1).cloned()
-                    })
-                    .collect();
+    /// Open a long-lived subscription for events tagging us: replies, mentions,
+    /// reactions, reposts and zap receipts.
+    pub async fn subscribe_notifications(&self, since: Timestamp) -> Result<(SubscriptionId, broadcast::Receiver<RelayPoolNotification>), NostratuiError> {
+        let notifications = self.client.notifications();
+
+        let filter = Filter::new()
+            .pubkey(self.my_key())
+            .kinds(notification::NOTIFICATION_KINDS)
+            .since(since);
+
+        let output = self.client.subscribe(filter, None).await?;
+        Ok((output.val, notifications))
+    }
+
+    pub async fn post_note(&self, note: String, reply_to: Option<ReplyTo>) -> Result<PublishResult,NostratuiError> {
+        let event = self.sign_note(note, reply_to, self.mining_job()).await?;
+        self.publish_event(&event).await
+    }
+
+    /// Build, mine and sign a note; mining stops with an error if `job` is cancelled
+    pub async fn sign_note(&self, note: String, reply_to: Option<ReplyTo>, job: Arc<MiningJob>) -> Result<Event,NostratuiError> {
+        // Notes quoted with nostr:nevent or nostr:note get a NIP-18 q tag each
+        let quotes = nip18::quote_tags(&note);
+        let mut builder = EventBuilder::text_note(note).tags(quotes);
+
+        // Replies carry NIP-10 root/reply markers and notify everyone in the thread
+        if let Some(reply_to) = reply_to {
+            builder = builder.tags(reply_to.tags(&self.my_key())?);
+        }
+
+        self.mine_and_sign(builder.build(self.my_key()), job).await
+    }
+
+    /// Build, mine and sign a NIP-25 reaction to `post`, `+` for a like and `-` for a dislike
+    pub async fn sign_reaction(&self, post: &Post, content: &str, job: Arc<MiningJob>) -> Result<Event,NostratuiError> {
+        let builder = EventBuilder::new(Kind::Reaction, content)
+            .tags(reaction::reaction_tags(post, self.relay_hint())?);
+        self.mine_and_sign(builder.build(self.my_key()), job).await
+    }
+
+    /// Build, mine and sign a NIP-18 repost of `post`, embedding `original` when we have its event
+    pub async fn sign_repost(&self, post: &Post, original: Option<&Event>, job: Arc<MiningJob>) -> Result<Event,NostratuiError> {
+        let content = original.map(|event| event.as_json()).unwrap_or_default();
+        let builder = EventBuilder::new(Kind::Repost, content)
+            .tags(nip18::repost_tags(post, self.relay_hint())?);
+        self.mine_and_sign(builder.build(self.my_key()), job).await
+    }
+
+    /// Fetch reactions to the given notes, in chunks like [`Self::fetch_events_by_id`].
+    ///
+    /// Each chunk stops at [`REACTION_FETCH_LIMIT`], so counts on very popular notes are a lower bound.
+    pub async fn fetch_reactions(&self, ids: &[EventId]) -> Result<Vec<Event>, NostratuiError> {
+        let mut events = Vec::new();
+        for chunk in ids.chunks(250) {
+            let filter = Filter::new().kind(Kind::Reaction).events(chunk.iter().copied()).limit(REACTION_FETCH_LIMIT);
+            events.extend(self.client.fetch_events(filter, Duration::from_secs(10)).await?);
+        }
+        Ok(events)
+    }
+
+    async fn mine_and_sign(&self, unsigned: UnsignedEvent, job: Arc<MiningJob>) -> Result<Event,NostratuiError> {
+        let difficulty = job.difficulty;
+        let mined = tokio::task::spawn_blocking(move || pow::mine(unsigned, &job))
+            .await
+            .map_err(|e| NostratuiError::Io(e.to_string()))?
+            .ok_or_else(|| NostratuiError::Cancelled(format!("mining {} bits of proof of work", difficulty)))?;
+        mined.sign_with_keys(&self.key)
+            .map_err(|e| NostratuiError::NostrSdk(e.to_string()))
+    }
+
+    /// Relay hint for notes we read, which all come from our own relays
+    pub fn relay_hint(&self) -> Option<String> {
+        self.relays.first().cloned()
+    }
+
+    /// Send a signed event to all relays, failing when none of them accepted it.
+    ///
+    /// When every relay turned it down and some asked for more proof of work,
+    /// the note is mined again to their difficulty and sent once more if
+    /// matching relay minimums is enabled. A note any relay took is left as it
+    /// is, a re-mined copy would be the same note under a second id; the
+    /// rejections are reported and later notes are mined to what was learned.
+    pub async fn publish_event(&self, event: &Event) -> Result<PublishResult,NostratuiError> {
+        let mut result = self.send_to_relays(event).await?;
+
+        if result.accepted.is_empty() && self.pow.match_relay {
+            let required = {
+                let learned = self.learned_pow.lock().unwrap_or_else(|e| e.into_inner());
+                result.rejected.iter()
+                    .filter_map(|(relay, _)| learned.get(relay.trim_end_matches('/')).copied())
+                    .max()
+            };
+            if let Some(required) = required.filter(|required| *required > pow::difficulty_of(&event.id.to_hex())) {
+                log::info!("Relays asked for {} bits of proof of work, mining the note again", required);
+                let unsigned = UnsignedEvent::new(event.pubkey, event.created_at, event.kind, event.tags.iter().cloned(), event.content.clone());
+                let remined = self.mine_and_sign(unsigned, MiningJob::new(required)).await?;
+                result = self.send_to_relays(&remined).await?;
+            }
+        }
+
+        if result.accepted.is_empty() {
+            let reasons = result.rejected.iter()
+                .map(|(url, reason)| format!("{}: {}", url, reason))
+                .collect::<Vec<String>>()
+                .join(", ");
+            return Err(NostratuiError::Network(format!("No relay accepted the event ({})", reasons)));
+        }
+        Ok(result)
+    }
+
+    async fn send_to_relays(&self, event: &Event) -> Result<PublishResult,NostratuiError> {
+        let started = Timestamp::now().as_u64();
+        let output = self.client.send_event(event).await?;
 
-                Post {
-                    id: event.id.to_hex(),
-                    user: event.pubkey.to_hex(),
-                    content: event.content,
-                    timestamp: event.created_at.as_u64(),
-                    datetime: event.created_at.to_human_datetime(),
-                    root_id,
-                    reply_id,
-                    mentions,
-                    participants,
+        let accepted: Vec<String> = output.success.iter().map(|url| url.to_string()).collect();
+        let rejected: Vec<(String, String)> = output.failed.iter()
+            .map(|(url, reason)| {
+                if let Some(required) = pow::required_by_rejection(reason) {
+                    let mut learned = self.learned_pow.lock().unwrap_or_else(|e| e.into_inner());
+                    learned.insert(url.as_str().trim_end_matches('/').to_string(), required);
                 }
+                // Some relays explain a rejection in a NOTICE rather than the OK message
+                let notices = self.relay_monitor.notices_since(url.as_str(), started);
+                let reason = if notices.is_empty() {
+                    reason.clone()
+                } else {
+                    format!("{} (NOTICE: {})", reason, notices.join("; "))
+                };
+                (url.to_string(), reason)
             })
             .collect();
-        
-        posts.sort_by_key(|post| post.timestamp);
-        
-        Ok(posts)
+        for (url, reason) in rejected.iter() {
+            self.relay_monitor.record_error(url, reason.clone());
+        }
+
+        Ok(PublishResult { event_id: output.val, accepted, rejected })
+    }
+
+    /// Turn a `note`, `nevent` or hex id into the [`ReplyTo`] expected by [`Self::post_note`]
+    pub async fn resolve_reply_target(&self, target: &str) -> Result<ReplyTo, NostratuiError> {
+        let (event_id, event, hint) = self.resolve_event(target).await?;
+        match event {
+            Some(event) => Ok(ReplyTo::from_event(&event, hint.or_else(|| self.relay_hint()))),
+            None => {
+                // Without the parent we can't know its root, so treat it as one
+                log::warn!("Reply target {} not found on relays", event_id.to_hex());
+                let mut reply_to = ReplyTo::from_id(&event_id.to_hex());
+                reply_to.root.relay = hint.clone();
+                reply_to.reply.relay = hint;
+                Ok(reply_to)
+            }
+        }
+    }
+
+    /// Look up the event a note, nevent or hex id refers to, along with the nevent's relay hint
+    pub async fn resolve_event(&self, target: &str) -> Result<(EventId, Option<Event>, Option<String>), NostratuiError> {
+        let (event_id, hint) = match EventId::parse(target) {
+            Ok(event_id) => (event_id, None),
+            Err(_) => Nip19Event::from_bech32(target.trim_start_matches("nostr:"))
+                .map(|nevent| (nevent.event_id, nevent.relays.first().map(|relay| relay.to_string())))
+                .map_err(|e| NostratuiError::KeyParsing(format!("Invalid event reference {}: {}", target, e)))?,
+        };
+
+        let filter = Filter::new().id(event_id);
+        let events = self.client.fetch_events(filter, Duration::from_secs(10)).await?;
+        Ok((event_id, events.first().cloned(), hint))
+    }
+
+    /// Load the whole thread around `selected`: its ancestors up to the root and every
+    /// note replying to any post in the thread.
+    ///
+    /// Posts are handed to `on_found` in batches as relays return them, so callers can
+    /// show the thread while it's still loading. The load gives up on whatever is
+    /// left after [`THREAD_TIMEOUT`].
+    pub async fn fetch_thread(&self, selected: &Post, mut on_found: impl FnMut(Vec<Post>) + Send) -> Result<(), NostratuiError> {
+        let deadline = tokio::time::Instant::now() + THREAD_TIMEOUT;
+        let mut seen: HashSet<String> = HashSet::new();
+        seen.insert(selected.id.clone());
+
+        // Ask for every referenced parent and root at once each round, most replies tag the root
+        // so this usually takes a round or two rather than one per ancestor
+        let parents_of = |post: &Post| [post.reply_id.clone(), post.root_id.clone()].into_iter().flatten();
+        let mut wanted: Vec<String> = parents_of(selected).collect();
+        for _ in 0..THREAD_MAX_DEPTH {
+            let ids: Vec<EventId> = wanted.drain(..)
+                .filter(|id| !seen.contains(id))
+                .filter_map(|id| EventId::from_hex(&id).ok())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+            if ids.is_empty() {
+                break;
+            }
+            let Some(events) = self.fetch_thread_round(Filter::new().ids(ids.iter().copied()), deadline).await? else {
+                break;
+            };
+            let ancestors: Vec<Post> = events.iter()
+                .filter(|event| seen.insert(event.id.to_hex()))
+                .map(Post::from_event)
+                .collect();
+            if ancestors.len() < ids.len() {
+                log::warn!("{} thread ancestor(s) not found on relays", ids.len() - ancestors.len());
+            }
+            wanted.extend(ancestors.iter().flat_map(parents_of));
+            if !ancestors.is_empty() {
+                on_found(ancestors);
+            }
+        }
+
+        // Collect descendants breadth first, since not every client tags the root
+        let mut frontier: Vec<EventId> = seen.iter()
+            .filter_map(|id| EventId::from_hex(id).ok())
+            .collect();
+        for _ in 0..THREAD_MAX_DEPTH {
+            if frontier.is_empty() {
+                break;
+            }
+            let mut found = Vec::new();
+            for chunk in frontier.chunks(THREAD_ID_CHUNK_SIZE) {
+                let filter = Filter::new()
+                    .kind(Kind::TextNote)
+                    .events(chunk.iter().copied());
+                let Some(events) = self.fetch_thread_round(filter, deadline).await? else {
+                    return Ok(());
+                };
+                let batch: Vec<Post> = events.iter()
+                    .filter(|event| seen.insert(event.id.to_hex()))
+                    .map(|event| {
+                        found.push(event.id);
+                        Post::from_event(event)
+                    })
+                    .collect();
+                if !batch.is_empty() {
+                    on_found(batch);
+                }
+            }
+            frontier = found;
+        }
+
+        Ok(())
+    }
+
+    // One request of a thread load, None once the load has run out of time
+    async fn fetch_thread_round(&self, filter: Filter, deadline: tokio::time::Instant) -> Result<Option<Vec<Event>>, NostratuiError> {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        if remaining.is_zero() {
+            log::warn!("Thread load took longer than {}s, showing what was found", THREAD_TIMEOUT.as_secs());
+            return Ok(None);
+        }
+        let events = self.client.fetch_events(filter, remaining.min(THREAD_ROUND_TIMEOUT)).await?;
+        Ok(Some(events.into_iter().collect()))
+    }
+
+}
+
+/// One REQ of a feed fetch, sent to a single relay
+struct FeedRequest {
+    relay: String,
+    authors: Vec<PublicKey>,
+    filter: Filter,
+}
+
+fn merge_feed_results(relays: &[String], results: &[(FeedRequest, Result<Vec<Event>, String>)]) -> FeedFetch {
+    let mut events: HashMap<EventId, Event> = HashMap::new();
+    let mut summaries: Vec<RelayFetchSummary> = relays.iter()
+        .map(|relay| RelayFetchSummary { relay: relay.clone(), ..RelayFetchSummary::default() })
+        .collect();
+
+    for (request, result) in results {
+        let summary = summaries.iter_mut()
+            .find(|summary| summary.relay == request.relay)
+            .expect("requests are only made to configured relays");
+        summary.requests += 1;
+        match result {
+            Ok(fetched) => {
+                summary.events += fetched.len();
+                for event in fetched {
+                    events.entry(event.id).or_insert_with(|| event.clone());
+                }
+            },
+            Err(e) => {
+                log::warn!("Error fetching posts from {}: {}", request.relay, e);
+                summary.errors.push(e.clone());
+            },
+        }
     }
 
+    let mut events: Vec<Event> = events.into_values().collect();
+    events.sort_by_key(|event| std::cmp::Reverse(event.created_at));
+    FeedFetch { events, relays: summaries }
 }
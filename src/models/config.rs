@@ -1,10 +1,25 @@
-use std::fs::{File, OpenOptions};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::{Write,BufReader};
+use std::path::PathBuf;
 use nostr_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
 use anyhow::{Context, Result};
 
 use crate::error::NostratuiError;
+use crate::models::pow::PowSettings;
+
+/// Where files the user would lose work with live, unlike the cache which may be wiped
+pub fn get_data_dir() -> Result<PathBuf, NostratuiError> {
+    let data_dir = dirs::data_dir()
+        .ok_or_else(|| NostratuiError::Config("Could not find data directory".to_string()))?
+        .join("nostratui");
+
+    fs::create_dir_all(&data_dir)
+        .map_err(|e| NostratuiError::Config(format!("Failed to create data directory: {}", e)))?;
+
+    Ok(data_dir)
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
@@ -12,9 +27,54 @@ pub struct Config {
     pub relays: Vec<String>,
     pub contacts: Vec<(String,String)>,
     pub last_login: Option<u64>,
+    /// Seconds before cached profile metadata is fetched again
+    #[serde(default = "default_profile_ttl")]
+    pub profile_ttl: u64,
+    /// Authors per feed filter
+    #[serde(default = "default_fetch_chunk_size")]
+    pub fetch_chunk_size: usize,
+    /// Feed requests in flight at once, across all relays
+    #[serde(default = "default_fetch_concurrency")]
+    pub fetch_concurrency: usize,
+    /// NIP-13 leading zero bits mined into published notes, 0 disables mining
+    #[serde(default = "default_pow_difficulty")]
+    pub pow_difficulty: u8,
+    /// Difficulty wanted by particular relays, notes are mined to the highest that applies
+    #[serde(default)]
+    pub relay_pow: HashMap<String, u8>,
+    /// Mine again when relays reject a note for too little work, and use their minimum from then on
+    #[serde(default)]
+    pub match_relay_pow: bool,
+    /// Hide incoming notes with less work than this, 0 shows everything
+    #[serde(default)]
+    pub min_incoming_pow: u8,
+}
+
+fn default_profile_ttl() -> u64 {
+    crate::models::profile::DEFAULT_PROFILE_TTL
+}
+
+fn default_fetch_chunk_size() -> usize {
+    crate::models::client::DEFAULT_FETCH_CHUNK_SIZE
+}
+
+fn default_fetch_concurrency() -> usize {
+    crate::models::client::DEFAULT_FETCH_CONCURRENCY
+}
+
+fn default_pow_difficulty() -> u8 {
+    crate::models::pow::DEFAULT_POW_DIFFICULTY
 }
 
 impl Config {
+    pub fn pow_settings(&self) -> PowSettings {
+        PowSettings {
+            difficulty: self.pow_difficulty,
+            relay_difficulty: self.relay_pow.clone(),
+            match_relay: self.match_relay_pow,
+        }
+    }
+
     pub fn load() -> Result<Self,NostratuiError> {
         let config_path = dirs::home_dir()
             .ok_or_else(|| NostratuiError::Config("Could not find home directory".to_string()))?
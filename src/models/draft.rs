@@ -0,0 +1,203 @@
+//! Unfinished notes and replies, one per reply target, kept until they are sent or deleted.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::ReplyTo;
+use crate::models::cache::get_legacy_drafts_file;
+use crate::models::config::get_data_dir;
+use crate::error::NostratuiError;
+
+/// Key of the draft for a note that isn't a reply
+pub const NEW_NOTE_KEY: &str = "new";
+/// Prefix of the keys of drafts quoting a note, followed by the quoted id
+pub const QUOTE_KEY_PREFIX: &str = "quote:";
+
+// Serializes read-modify-write cycles between the TUI and editor sessions
+static DRAFTS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    /// The note being replied to, None for a new note
+    pub reply_to: Option<ReplyTo>,
+    /// Hex id of the note being quoted, if any
+    #[serde(default)]
+    pub quote: Option<String>,
+    pub content: String,
+    pub updated_at: u64,
+}
+
+impl Draft {
+    pub fn key(&self) -> String {
+        key_for(self.reply_to.as_ref(), self.quote.as_deref())
+    }
+
+    /// How the draft is referred to on the command line: `new`, the parent's note id
+    /// or `quote:` and the quoted note id
+    pub fn display_key(&self) -> String {
+        match (&self.reply_to, &self.quote) {
+            (Some(reply_to), _) => note_id(&reply_to.reply.id),
+            (None, Some(quote)) => format!("{}{}", QUOTE_KEY_PREFIX, note_id(quote)),
+            (None, None) => NEW_NOTE_KEY.to_string(),
+        }
+    }
+}
+
+fn note_id(id: &str) -> String {
+    EventId::from_hex(id)
+        .ok()
+        .and_then(|id| id.to_bech32().ok())
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Drafts are keyed by the id of the note they reply to or quote
+pub fn key_for(reply_to: Option<&ReplyTo>, quote: Option<&str>) -> String {
+    match (reply_to, quote) {
+        (Some(reply_to), _) => reply_to.reply.id.clone(),
+        (None, Some(quote)) => format!("{}{}", QUOTE_KEY_PREFIX, quote),
+        (None, None) => NEW_NOTE_KEY.to_string(),
+    }
+}
+
+/// Turn `new`, a hex id, a note or a nevent, optionally after `quote:`, into a draft key
+pub fn parse_key(target: &str) -> Result<String, NostratuiError> {
+    if target == NEW_NOTE_KEY {
+        return Ok(NEW_NOTE_KEY.to_string());
+    }
+    let (prefix, reference) = match target.strip_prefix(QUOTE_KEY_PREFIX) {
+        Some(quoted) => (QUOTE_KEY_PREFIX, quoted),
+        None => ("", target),
+    };
+    let reference = reference.trim_start_matches("nostr:");
+    EventId::parse(reference)
+        .or_else(|_| Nip19Event::from_bech32(reference).map(|nevent| nevent.event_id))
+        .map(|id| format!("{}{}", prefix, id.to_hex()))
+        .map_err(|e| NostratuiError::KeyParsing(format!("Invalid draft {}: {}", target, e)))
+}
+
+/// Drafts live in the data directory, those left in the cache by earlier versions are moved there
+pub fn get_drafts_file() -> Result<PathBuf, NostratuiError> {
+    let drafts_path = get_data_dir()?.join("drafts.json");
+    let legacy_path = get_legacy_drafts_file()?;
+    if legacy_path.exists() && !drafts_path.exists() {
+        // Copy rather than rename, the two directories may be on different filesystems
+        fs::copy(&legacy_path, &drafts_path)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to move drafts out of the cache: {}", e)))?;
+        fs::remove_file(&legacy_path)?;
+    }
+    Ok(drafts_path)
+}
+
+/// Every draft, most recently edited first
+pub fn load_drafts() -> Result<Vec<Draft>, NostratuiError> {
+    let drafts_path = get_drafts_file()?;
+    if !drafts_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let drafts_data = fs::read_to_string(drafts_path)?;
+    let mut drafts: Vec<Draft> = serde_json::from_str(&drafts_data)?;
+    drafts.sort_by_key(|draft| std::cmp::Reverse(draft.updated_at));
+    Ok(drafts)
+}
+
+pub fn get_draft(key: &str) -> Result<Option<Draft>, NostratuiError> {
+    Ok(load_drafts()?.into_iter().find(|draft| draft.key() == key))
+}
+
+fn save_drafts(drafts: &[Draft]) -> Result<(), NostratuiError> {
+    let json = serde_json::to_string_pretty(drafts)
+        .map_err(|e| NostratuiError::Cache(format!("Failed to serialize drafts: {}", e)))?;
+
+    fs::write(get_drafts_file()?, json)
+        .map_err(|e| NostratuiError::Cache(format!("Failed to write drafts file: {}", e)))
+}
+
+fn update_drafts<F>(f: F) -> Result<(), NostratuiError>
+where
+    F: FnOnce(&mut Vec<Draft>),
+{
+    let _guard = DRAFTS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut drafts = load_drafts()?;
+    f(&mut drafts);
+    save_drafts(&drafts)
+}
+
+/// Keep `content` as the draft for `reply_to` or `quote`, replacing any earlier one.
+///
+/// Blank content deletes the draft instead.
+pub fn save_draft(content: &str, reply_to: Option<&ReplyTo>, quote: Option<&str>) -> Result<(), NostratuiError> {
+    let key = key_for(reply_to, quote);
+    if content.trim().is_empty() {
+        return delete_draft(&key);
+    }
+    let draft = Draft {
+        reply_to: reply_to.cloned(),
+        quote: quote.map(str::to_string),
+        content: content.to_string(),
+        updated_at: Timestamp::now().as_u64(),
+    };
+    update_drafts(|drafts| {
+        drafts.retain(|draft| draft.key() != key);
+        drafts.push(draft);
+    })
+}
+
+pub fn delete_draft(key: &str) -> Result<(), NostratuiError> {
+    update_drafts(|drafts| drafts.retain(|draft| draft.key() != key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_support::{note_id as event_id, NOTE_ID as ID};
+
+    #[test]
+    fn new_note_key() {
+        assert_eq!(parse_key("new").unwrap(), NEW_NOTE_KEY);
+    }
+
+    #[test]
+    fn reply_keys_in_every_form() {
+        let note = event_id().to_bech32().unwrap();
+        let nevent = Nip19Event::new(event_id()).to_bech32().unwrap();
+        assert_eq!(parse_key(ID).unwrap(), ID);
+        assert_eq!(parse_key(&note).unwrap(), ID);
+        assert_eq!(parse_key(&format!("nostr:{}", note)).unwrap(), ID);
+        assert_eq!(parse_key(&nevent).unwrap(), ID);
+    }
+
+    #[test]
+    fn quote_keys_keep_the_prefix() {
+        let note = event_id().to_bech32().unwrap();
+        let expected = format!("quote:{}", ID);
+        assert_eq!(parse_key(&format!("quote:{}", ID)).unwrap(), expected);
+        assert_eq!(parse_key(&format!("quote:{}", note)).unwrap(), expected);
+        assert_eq!(parse_key(&format!("quote:nostr:{}", note)).unwrap(), expected);
+        assert_eq!(key_for(None, Some(ID)), expected);
+    }
+
+    #[test]
+    fn display_key_parses_back_to_the_key() {
+        let draft = Draft {
+            reply_to: None,
+            quote: Some(ID.to_string()),
+            content: String::from("quoting"),
+            updated_at: 0,
+        };
+        assert_eq!(parse_key(&draft.display_key()).unwrap(), draft.key());
+
+        let reply = Draft { reply_to: Some(ReplyTo::from_id(ID)), quote: None, ..draft };
+        assert_eq!(parse_key(&reply.display_key()).unwrap(), reply.key());
+    }
+
+    #[test]
+    fn invalid_keys() {
+        assert!(parse_key("quote:").is_err());
+        assert!(parse_key("newest").is_err());
+        assert!(parse_key("note1nothing").is_err());
+    }
+}
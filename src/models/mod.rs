@@ -2,8 +2,28 @@ pub mod post;
 pub mod client;
 pub mod config;
 pub mod cache;
+pub mod store;
+pub mod outbox;
+pub mod profile;
+pub mod sync;
+pub mod nip10;
+pub mod relay;
+pub mod pow;
+pub mod draft;
+pub mod reaction;
+pub mod nip18;
+pub mod notification;
+#[cfg(test)]
+pub mod test_support;
 
 // Re-export important structs for convenience
 pub use post::Post;
-pub use client::NostrClient;
+pub use client::{NostrClient, FeedQuery, FeedFetch, PublishResult, RelayFetchSummary};
 pub use config::Config;
+pub use profile::{Profile, ProfileStore};
+pub use sync::SyncState;
+pub use nip10::ReplyTo;
+pub use relay::RelayHealth;
+pub use draft::Draft;
+pub use reaction::{ReactionCounts, ReactionStore};
+pub use notification::{Notification, NotificationKind, NotificationStore};
@@ -0,0 +1,383 @@
+//! NIP-10 thread references: reading `e`/`p` tags and building them for replies.
+//!
+//! Both the marked form (`["e", <id>, <relay>, "root" | "reply" | "mention", <pubkey>]`)
+//! and the deprecated positional form, where the first `e` tag is the root and the
+//! last one the parent, are understood when parsing. Replies are always written in
+//! the marked form.
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::error::NostratuiError;
+
+/// An `e` tag pointing at another note
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventRef {
+    pub id: String,
+    /// Relay the note can be found on, empty hints are dropped
+    pub relay: Option<String>,
+    /// Author of the note when the tag carries it
+    pub author: Option<String>,
+}
+
+impl EventRef {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self { id: id.into(), relay: None, author: None }
+    }
+}
+
+/// Where a note sits in a thread, as described by its tags
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadRefs {
+    pub root: Option<EventRef>,
+    /// The note being replied to, the same as `root` for direct replies
+    pub reply: Option<EventRef>,
+    pub mentions: Vec<EventRef>,
+    /// Hex keys from `p` tags, in tag order without duplicates
+    pub participants: Vec<String>,
+}
+
+impl ThreadRefs {
+    pub fn from_event(event: &Event) -> Self {
+        Self::parse(event.tags.iter())
+    }
+
+    pub fn parse<'a>(tags: impl IntoIterator<Item = &'a Tag>) -> Self {
+        let mut refs = ThreadRefs::default();
+        let mut marked = Vec::new();
+        let mut positional = Vec::new();
+
+        for tag in tags {
+            let values = tag.as_slice();
+            match values.first().map(String::as_str) {
+                Some("e") => {
+                    let Some(event_ref) = parse_event_ref(values) else {
+                        continue;
+                    };
+                    match values.get(3).map(String::as_str) {
+                        Some(marker @ ("root" | "reply" | "mention")) => marked.push((marker.to_string(), event_ref)),
+                        _ => positional.push(event_ref),
+                    }
+                },
+                Some("p") => {
+                    if let Some(key) = values.get(1).filter(|key| PublicKey::from_hex(key).is_ok()) {
+                        if !refs.participants.contains(key) {
+                            refs.participants.push(key.clone());
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if marked.iter().any(|(marker, _)| marker != "mention") {
+            // Marked form: unmarked tags next to marked ones are only mentions
+            for (marker, event_ref) in marked {
+                match marker.as_str() {
+                    "root" if refs.root.is_none() => refs.root = Some(event_ref),
+                    "reply" if refs.reply.is_none() => refs.reply = Some(event_ref),
+                    _ => refs.mentions.push(event_ref),
+                }
+            }
+            refs.mentions.extend(positional);
+            // A direct reply to the root only carries the root marker
+            if refs.reply.is_none() {
+                refs.reply = refs.root.clone();
+            }
+            // Some clients mark the parent but leave out the root
+            if refs.root.is_none() {
+                refs.root = refs.reply.clone();
+            }
+        } else {
+            // Positional form: first is the root, last is the parent, anything between is a mention
+            refs.mentions.extend(marked.into_iter().map(|(_, event_ref)| event_ref));
+            let mut positional = positional.into_iter();
+            refs.root = positional.next();
+            refs.reply = positional.next_back().or_else(|| refs.root.clone());
+            refs.mentions.extend(positional);
+        }
+
+        refs
+    }
+}
+
+fn parse_event_ref(values: &[String]) -> Option<EventRef> {
+    let id = values.get(1).filter(|id| EventId::from_hex(id).is_ok())?;
+    let relay = values.get(2)
+        .map(|relay| relay.trim())
+        .filter(|relay| !relay.is_empty())
+        .map(str::to_string);
+    let author = values.get(4).filter(|key| PublicKey::from_hex(key).is_ok()).cloned();
+    Some(EventRef { id: id.to_lowercase(), relay, author })
+}
+
+/// Everything needed to tag a reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplyTo {
+    pub root: EventRef,
+    pub reply: EventRef,
+    /// Everyone to notify: the parent's author followed by the parent's own `p` tags
+    pub participants: Vec<String>,
+}
+
+impl ReplyTo {
+    /// Reply to `parent`, which can be found on `relay`
+    pub fn from_event(parent: &Event, relay: Option<String>) -> Self {
+        let refs = ThreadRefs::from_event(parent);
+        let reply = EventRef {
+            id: parent.id.to_hex(),
+            relay,
+            author: Some(parent.pubkey.to_hex()),
+        };
+        let participants = std::iter::once(parent.pubkey.to_hex())
+            .chain(refs.participants)
+            .collect();
+        Self::new(refs.root.unwrap_or_else(|| reply.clone()), reply, participants)
+    }
+
+    /// Reply to a post when its original event isn't at hand, so tags lack relay hints
+    pub fn from_post(parent: &crate::models::Post) -> Self {
+        let reply = EventRef {
+            id: parent.id.clone(),
+            relay: None,
+            author: Some(parent.pubkey.clone()).filter(|key| PublicKey::from_hex(key).is_ok()),
+        };
+        let root = parent.root_id.clone().map(EventRef::new).unwrap_or_else(|| reply.clone());
+        let participants = reply.author.iter()
+            .chain(parent.participants.iter())
+            .cloned()
+            .collect();
+        Self::new(root, reply, participants)
+    }
+
+    /// Reply to a note nothing is known about, treating it as the root of its thread
+    pub fn from_id(id: &str) -> Self {
+        let reply = EventRef::new(id);
+        Self::new(reply.clone(), reply, Vec::new())
+    }
+
+    fn new(root: EventRef, reply: EventRef, participants: Vec<String>) -> Self {
+        let mut unique: Vec<String> = Vec::new();
+        for key in participants {
+            if !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+        Self { root, reply, participants: unique }
+    }
+
+    /// Marked `e` tags for the root and parent plus a `p` tag for each participant but `me`
+    pub fn tags(&self, me: &PublicKey) -> Result<Vec<Tag>, NostratuiError> {
+        let mut tags = vec![event_tag(&self.root, "root")?];
+        if self.reply.id != self.root.id {
+            tags.push(event_tag(&self.reply, "reply")?);
+        }
+        let me = me.to_hex();
+        for key in self.participants.iter().filter(|key| **key != me) {
+            tags.push(Tag::parse(["p", key.as_str()])
+                .map_err(|e| NostratuiError::NostrSdk(e.to_string()))?);
+        }
+        Ok(tags)
+    }
+}
+
+/// Id in the last valid `e` tag, where reactions and reposts name the note they are about
+pub fn last_event_tag(event: &Event) -> Option<String> {
+    event.tags.iter()
+        .filter(|tag| tag.kind() == TagKind::e())
+        .filter_map(|tag| tag.content())
+        .filter(|id| EventId::from_hex(id).is_ok())
+        .last()
+        .map(|id| id.to_lowercase())
+}
+
+fn event_tag(event_ref: &EventRef, marker: &str) -> Result<Tag, NostratuiError> {
+    let mut values = vec![
+        "e".to_string(),
+        event_ref.id.clone(),
+        event_ref.relay.clone().unwrap_or_default(),
+        marker.to_string(),
+    ];
+    if let Some(author) = &event_ref.author {
+        values.push(author.clone());
+    }
+    Tag::parse(values).map_err(|e| NostratuiError::NostrSdk(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_support::{tag, NOTE_ID as ROOT};
+
+    const MIDDLE: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+    const PARENT: &str = "3333333333333333333333333333333333333333333333333333333333333333";
+    const ALICE: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const BOB: &str = "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    fn tags(raw: &[&[&str]]) -> Vec<Tag> {
+        raw.iter().map(|values| tag(values)).collect()
+    }
+
+    fn parse(raw: &[&[&str]]) -> ThreadRefs {
+        ThreadRefs::parse(tags(raw).iter())
+    }
+
+    #[test]
+    fn marked_root_and_reply() {
+        let refs = parse(&[
+            &["e", ROOT, "wss://relay.example", "root"],
+            &["e", PARENT, "", "reply"],
+            &["p", ALICE],
+        ]);
+        assert_eq!(refs.root.as_ref().unwrap().id, ROOT);
+        assert_eq!(refs.root.as_ref().unwrap().relay.as_deref(), Some("wss://relay.example"));
+        assert_eq!(refs.reply.as_ref().unwrap().id, PARENT);
+        assert_eq!(refs.reply.as_ref().unwrap().relay, None);
+        assert!(refs.mentions.is_empty());
+        assert_eq!(refs.participants, vec![ALICE.to_string()]);
+    }
+
+    #[test]
+    fn marked_direct_reply_to_root() {
+        let refs = parse(&[&["e", ROOT, "", "root"]]);
+        assert_eq!(refs.root.unwrap().id, ROOT);
+        assert_eq!(refs.reply.unwrap().id, ROOT);
+    }
+
+    #[test]
+    fn marked_reply_without_root() {
+        let refs = parse(&[&["e", PARENT, "", "reply"]]);
+        assert_eq!(refs.root.unwrap().id, PARENT);
+        assert_eq!(refs.reply.unwrap().id, PARENT);
+    }
+
+    #[test]
+    fn marked_with_mentions_and_author() {
+        let refs = parse(&[
+            &["e", MIDDLE, "", "mention"],
+            &["e", ROOT, "wss://relay.example", "root", ALICE],
+            &["e", PARENT, "wss://other.example", "reply", BOB],
+        ]);
+        assert_eq!(refs.root.as_ref().unwrap().author.as_deref(), Some(ALICE));
+        assert_eq!(refs.reply.as_ref().unwrap().author.as_deref(), Some(BOB));
+        assert_eq!(refs.mentions, vec![EventRef::new(MIDDLE)]);
+    }
+
+    #[test]
+    fn unmarked_tags_next_to_marked_ones_are_mentions() {
+        let refs = parse(&[
+            &["e", ROOT, "", "root"],
+            &["e", MIDDLE],
+        ]);
+        assert_eq!(refs.root.unwrap().id, ROOT);
+        assert_eq!(refs.reply.unwrap().id, ROOT);
+        assert_eq!(refs.mentions, vec![EventRef::new(MIDDLE)]);
+    }
+
+    #[test]
+    fn positional_single_tag_is_the_parent() {
+        let refs = parse(&[&["e", PARENT]]);
+        assert_eq!(refs.root.unwrap().id, PARENT);
+        assert_eq!(refs.reply.unwrap().id, PARENT);
+    }
+
+    #[test]
+    fn positional_with_relay_hints() {
+        let refs = parse(&[
+            &["e", ROOT, "wss://relay.example"],
+            &["e", PARENT, "wss://other.example"],
+        ]);
+        assert_eq!(refs.root.as_ref().unwrap().id, ROOT);
+        assert_eq!(refs.root.unwrap().relay.as_deref(), Some("wss://relay.example"));
+        assert_eq!(refs.reply.as_ref().unwrap().id, PARENT);
+        assert_eq!(refs.reply.unwrap().relay.as_deref(), Some("wss://other.example"));
+    }
+
+    #[test]
+    fn positional_middle_tags_are_mentions() {
+        let refs = parse(&[
+            &["e", ROOT],
+            &["e", MIDDLE, ""],
+            &["e", PARENT],
+        ]);
+        assert_eq!(refs.root.unwrap().id, ROOT);
+        assert_eq!(refs.reply.unwrap().id, PARENT);
+        assert_eq!(refs.mentions, vec![EventRef::new(MIDDLE)]);
+    }
+
+    #[test]
+    fn only_mention_markers_fall_back_to_positional() {
+        let refs = parse(&[
+            &["e", MIDDLE, "", "mention"],
+            &["e", PARENT],
+        ]);
+        assert_eq!(refs.root.unwrap().id, PARENT);
+        assert_eq!(refs.reply.unwrap().id, PARENT);
+        assert_eq!(refs.mentions, vec![EventRef::new(MIDDLE)]);
+    }
+
+    #[test]
+    fn invalid_ids_and_keys_are_skipped() {
+        let refs = parse(&[
+            &["e", "not-an-id", "", "root"],
+            &["e", PARENT],
+            &["p", "npub-ish"],
+            &["p", BOB],
+            &["p", BOB, "wss://relay.example"],
+        ]);
+        assert_eq!(refs.root.unwrap().id, PARENT);
+        assert_eq!(refs.participants, vec![BOB.to_string()]);
+    }
+
+    #[test]
+    fn notes_without_e_tags_are_roots() {
+        let refs = parse(&[&["p", ALICE], &["t", "nostr"]]);
+        assert_eq!(refs.root, None);
+        assert_eq!(refs.reply, None);
+    }
+
+    #[test]
+    fn reply_tags_carry_markers_hints_and_participants() {
+        let me = Keys::generate().public_key();
+        let reply_to = ReplyTo::new(
+            EventRef { id: ROOT.to_string(), relay: Some("wss://relay.example".to_string()), author: None },
+            EventRef { id: PARENT.to_string(), relay: None, author: Some(ALICE.to_string()) },
+            vec![ALICE.to_string(), BOB.to_string(), me.to_hex(), BOB.to_string()],
+        );
+        let built: Vec<Vec<String>> = reply_to.tags(&me).unwrap()
+            .into_iter()
+            .map(|tag| tag.to_vec())
+            .collect();
+        assert_eq!(built, vec![
+            vec!["e".to_string(), ROOT.to_string(), "wss://relay.example".to_string(), "root".to_string()],
+            vec!["e".to_string(), PARENT.to_string(), String::new(), "reply".to_string(), ALICE.to_string()],
+            vec!["p".to_string(), ALICE.to_string()],
+            vec!["p".to_string(), BOB.to_string()],
+        ]);
+    }
+
+    #[test]
+    fn reply_to_root_only_tags_the_root() {
+        let me = Keys::generate().public_key();
+        let built = ReplyTo::from_id(ROOT).tags(&me).unwrap();
+        assert_eq!(built.len(), 1);
+        assert_eq!(built[0].as_slice()[3], "root");
+    }
+
+    #[test]
+    fn reply_to_event_inherits_root_and_participants() {
+        let keys = Keys::generate();
+        let parent = EventBuilder::text_note("parent")
+            .tags(tags(&[
+                &["e", ROOT, "wss://relay.example", "root"],
+                &["p", BOB],
+            ]))
+            .sign_with_keys(&keys)
+            .unwrap();
+        let reply_to = ReplyTo::from_event(&parent, Some("wss://read.example".to_string()));
+        assert_eq!(reply_to.root.id, ROOT);
+        assert_eq!(reply_to.root.relay.as_deref(), Some("wss://relay.example"));
+        assert_eq!(reply_to.reply.id, parent.id.to_hex());
+        assert_eq!(reply_to.reply.relay.as_deref(), Some("wss://read.example"));
+        assert_eq!(reply_to.participants, vec![keys.public_key().to_hex(), BOB.to_string()]);
+    }
+}
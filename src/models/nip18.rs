@@ -0,0 +1,166 @@
+//! NIP-18 reposts and quotes: kind 6 events carrying the original note, and `q` tags
+//! for notes that reference others with `nostr:nevent` or `nostr:note`.
+
+use nostr_sdk::prelude::*;
+use crate::models::Post;
+use crate::models::nip10::last_event_tag;
+use crate::error::NostratuiError;
+
+/// `e` and `p` tags for reposting `post`
+pub fn repost_tags(post: &Post, relay: Option<String>) -> Result<Vec<Tag>, NostratuiError> {
+    let parse = |values: Vec<String>| Tag::parse(values).map_err(|e| NostratuiError::NostrSdk(e.to_string()));
+    Ok(vec![
+        parse(vec!["e".to_string(), post.id.clone(), relay.unwrap_or_default()])?,
+        parse(vec!["p".to_string(), post.pubkey.clone()])?,
+    ])
+}
+
+/// `nostr:nevent1…` for the note `id`, with its author and a relay to find it on when known
+pub fn nevent_uri(id: &str, author: Option<&str>, relay: Option<String>) -> Result<String, NostratuiError> {
+    let id = EventId::from_hex(id).map_err(|e| NostratuiError::KeyParsing(e.to_string()))?;
+    let mut nevent = Nip19Event::new(id).kind(Kind::TextNote);
+    if let Some(author) = author.and_then(|author| PublicKey::from_hex(author).ok()) {
+        nevent = nevent.author(author);
+    }
+    if let Some(relay) = relay.and_then(|relay| RelayUrl::parse(&relay).ok()) {
+        nevent = nevent.relays([relay]);
+    }
+    nevent.to_bech32()
+        .map(|bech32| format!("nostr:{}", bech32))
+        .map_err(|e| NostratuiError::NostrSdk(e.to_string()))
+}
+
+/// A `q` tag for each note quoted in `content`, once per note
+pub fn quote_tags(content: &str) -> Vec<Tag> {
+    let mut quoted: Vec<(EventId, Option<RelayUrl>, Option<PublicKey>)> = Vec::new();
+    for word in content.split(|c: char| c.is_whitespace() || "()[],;".contains(c)) {
+        // Quotes and other punctuation may come right before the URI too
+        let Some((_, reference)) = word.split_once("nostr:") else {
+            continue;
+        };
+        // Bech32 is alphanumeric, whatever punctuation follows isn't part of the reference
+        let reference = reference.split(|c: char| !c.is_ascii_alphanumeric()).next().unwrap_or_default();
+        let parsed = if let Ok(nevent) = Nip19Event::from_bech32(reference) {
+            Some((nevent.event_id, nevent.relays.first().cloned(), nevent.author))
+        } else if reference.starts_with("note1") {
+            EventId::from_bech32(reference).ok().map(|id| (id, None, None))
+        } else {
+            None
+        };
+        if let Some(parsed) = parsed.filter(|(id, _, _)| quoted.iter().all(|(seen, _, _)| seen != id)) {
+            quoted.push(parsed);
+        }
+    }
+
+    quoted.into_iter()
+        .filter_map(|(id, relay, author)| {
+            let mut values = vec![
+                "q".to_string(),
+                id.to_hex(),
+                relay.map(|relay| relay.to_string()).unwrap_or_default(),
+            ];
+            if let Some(author) = author {
+                values.push(author.to_hex());
+            }
+            Tag::parse(values).ok()
+        })
+        .collect()
+}
+
+/// The original note embedded in a repost, when it's there and is the note the repost tags.
+///
+/// Its signature isn't checked here, reposts are checked once with [`has_valid_embed`]
+/// when they arrive.
+pub fn embedded_note(event: &Event) -> Option<Event> {
+    let original = Event::from_json(&event.content).ok()?;
+    let expected = last_event_tag(event);
+    (original.kind == Kind::TextNote && expected.is_none_or(|id| id == original.id.to_hex()))
+        .then_some(original)
+}
+
+/// False for a repost embedding a note whose signature doesn't hold, true for anything else
+pub fn has_valid_embed(event: &Event) -> bool {
+    event.kind != Kind::Repost || embedded_note(event).is_none_or(|original| original.verify().is_ok())
+}
+
+/// Fill in the reposted note of every repost in `posts` that `originals` has
+pub fn fill_reposts(posts: &mut [Post], originals: &[Post]) {
+    for post in posts.iter_mut().filter(|post| post.repost.is_none()) {
+        if let Some(original) = originals.iter().find(|original| post.reposted_id.as_ref() == Some(&original.id)) {
+            post.set_repost(original.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_support::{forged, note, note_id as event_id, repost, sign, NOTE_ID as ID};
+
+    fn quoted_ids(content: &str) -> Vec<String> {
+        quote_tags(content).iter()
+            .map(|tag| tag.as_slice()[1].clone())
+            .collect()
+    }
+
+    #[test]
+    fn references_followed_by_punctuation() {
+        let note = event_id().to_bech32().unwrap();
+        let nevent = Nip19Event::new(event_id()).to_bech32().unwrap();
+        for content in [
+            format!("see nostr:{}.", note),
+            format!("see nostr:{}, then", note),
+            format!("(nostr:{})", note),
+            format!("wow nostr:{}!", nevent),
+            format!("\"nostr:{}\"", nevent),
+            format!("nostr:{}:", nevent),
+        ] {
+            assert_eq!(quoted_ids(&content), vec![ID.to_string()], "{}", content);
+        }
+    }
+
+    #[test]
+    fn each_note_quoted_once_with_its_hints() {
+        let author = Keys::generate().public_key();
+        let relay = RelayUrl::parse("wss://relay.example").unwrap();
+        let nevent = Nip19Event::new(event_id()).author(author).relays([relay]).to_bech32().unwrap();
+        let note = event_id().to_bech32().unwrap();
+        let tags = quote_tags(&format!("nostr:{} and again nostr:{}", nevent, note));
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].as_slice(), &["q".to_string(), ID.to_string(), "wss://relay.example".to_string(), author.to_hex()]);
+    }
+
+    #[test]
+    fn ignores_other_references() {
+        let npub = Keys::generate().public_key().to_bech32().unwrap();
+        assert!(quote_tags(&format!("hi nostr:{} and nostr:note1broken", npub)).is_empty());
+    }
+
+    #[test]
+    fn embedded_note_must_match_the_e_tag() {
+        let keys = Keys::generate();
+        let original = note(&keys, "original");
+        let embedded = embedded_note(&repost(&original.as_json(), &original.id.to_hex(), &keys)).unwrap();
+        assert_eq!(embedded.id, original.id);
+        assert!(embedded_note(&repost(&original.as_json(), ID, &keys)).is_none());
+    }
+
+    #[test]
+    fn embedded_note_must_be_a_text_note() {
+        let keys = Keys::generate();
+        let metadata = sign(Kind::Metadata, "{}", Vec::new(), &keys);
+        assert!(embedded_note(&repost(&metadata.as_json(), &metadata.id.to_hex(), &keys)).is_none());
+
+        let empty = sign(Kind::Repost, "", Vec::new(), &keys);
+        assert!(embedded_note(&empty).is_none());
+        assert!(has_valid_embed(&empty));
+    }
+
+    #[test]
+    fn forged_embeds_are_caught_on_arrival() {
+        let keys = Keys::generate();
+        let original = note(&keys, "original");
+        assert!(has_valid_embed(&repost(&original.as_json(), &original.id.to_hex(), &keys)));
+        assert!(!has_valid_embed(&repost(&forged(&original, "forged"), &original.id.to_hex(), &keys)));
+    }
+}
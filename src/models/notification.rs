@@ -0,0 +1,402 @@
+//! Events that tag us: replies, mentions, reactions, reposts and zap receipts, with
+//! which of them have been read kept between sessions.
+
+use std::fs;
+use std::path::PathBuf;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Post;
+use crate::models::cache::get_cache_dir;
+use crate::models::nip10::{last_event_tag, ThreadRefs};
+use crate::error::NostratuiError;
+
+/// Notifications kept on disk, the oldest are dropped first
+const MAX_NOTIFICATIONS: usize = 500;
+/// How far back to look the first time, before anything has been received
+const INITIAL_LOOKBACK_SECS: u64 = 60 * 60 * 24 * 7;
+/// Refetch a little before the newest notification in case a relay indexed it late
+const OVERLAP_SECS: u64 = 5 * 60;
+
+/// Event kinds that can notify us
+pub const NOTIFICATION_KINDS: [Kind; 4] = [Kind::TextNote, Kind::Repost, Kind::Reaction, Kind::ZapReceipt];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NotificationKind {
+    Reply,
+    Mention,
+    Reaction,
+    Repost,
+    Zap,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 5] = [
+        NotificationKind::Reply,
+        NotificationKind::Mention,
+        NotificationKind::Reaction,
+        NotificationKind::Repost,
+        NotificationKind::Zap,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NotificationKind::Reply => "Replies",
+            NotificationKind::Mention => "Mentions",
+            NotificationKind::Reaction => "Reactions",
+            NotificationKind::Repost => "Reposts",
+            NotificationKind::Zap => "Zaps",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: String,
+    pub kind: NotificationKind,
+    /// Hex key of who it's from, the zapper rather than their wallet for zaps
+    pub author: String,
+    pub created_at: u64,
+    /// Note text, the reaction, or the zap amount and comment
+    pub content: String,
+    /// Our note it is about, for reactions, reposts and zaps
+    pub target: Option<String>,
+    /// The note itself, for replies and mentions
+    pub post: Option<Post>,
+    pub read: bool,
+}
+
+impl Notification {
+    /// Make a notification from an event tagging `me`, None for our own events or other kinds
+    pub fn from_event(event: &Event, me: &PublicKey) -> Option<Self> {
+        let (kind, author, content, post) = match event.kind {
+            _ if event.pubkey == *me && event.kind != Kind::ZapReceipt => return None,
+            Kind::TextNote => {
+                let kind = if ThreadRefs::from_event(event).reply.is_some() {
+                    NotificationKind::Reply
+                } else {
+                    NotificationKind::Mention
+                };
+                (kind, event.pubkey.to_hex(), event.content.to_string(), Some(Post::from_event(event)))
+            },
+            Kind::Reaction => (NotificationKind::Reaction, event.pubkey.to_hex(), event.content.to_string(), None),
+            Kind::Repost => (NotificationKind::Repost, event.pubkey.to_hex(), String::new(), None),
+            Kind::ZapReceipt => {
+                // Anyone can publish a receipt, so only one carrying a zap request signed by the zapper counts
+                let request = zap_request(event)?;
+                if request.pubkey == *me {
+                    return None;
+                }
+                (NotificationKind::Zap, request.pubkey.to_hex(), describe_zap(event, &request)?, None)
+            },
+            _ => return None,
+        };
+
+        Some(Self {
+            id: event.id.to_hex(),
+            kind,
+            author,
+            // A sender's clock ahead of ours would otherwise hold back where the next session starts
+            created_at: event.created_at.as_u64().min(Timestamp::now().as_u64()),
+            content,
+            target: match kind {
+                NotificationKind::Reply | NotificationKind::Mention => None,
+                _ => last_event_tag(event),
+            },
+            post,
+            read: false,
+        })
+    }
+}
+
+/// The zap request a receipt carries in its `description` tag, when it is one and its signature holds
+fn zap_request(receipt: &Event) -> Option<Event> {
+    let description = receipt.tags.iter()
+        .find(|tag| tag.kind() == TagKind::Description)
+        .and_then(|tag| tag.content())?;
+    let request = Event::from_json(description).ok()?;
+    (request.kind == Kind::ZapRequest && request.verify().is_ok()).then_some(request)
+}
+
+// `21 sats: comment`, the amount from the invoice that was paid.
+//
+// None when the request asked for a different amount than the invoice, a receipt
+// claiming more than was paid.
+fn describe_zap(receipt: &Event, request: &Event) -> Option<String> {
+    let requested = request.tags.iter()
+        .find(|tag| tag.kind() == TagKind::Amount)
+        .and_then(|tag| tag.content())
+        .and_then(|msats| msats.parse::<u64>().ok());
+    let invoiced = receipt.tags.iter()
+        .find(|tag| tag.kind() == TagKind::Bolt11)
+        .and_then(|tag| tag.content())
+        .and_then(bolt11_msats);
+    if let (Some(requested), Some(invoiced)) = (requested, invoiced) {
+        if requested != invoiced {
+            return None;
+        }
+    }
+    let amount = invoiced
+        .map_or_else(|| String::from("zap"), |msats| format!("{} sats", msats / 1000));
+    Some(match Some(request.content.trim()).filter(|comment| !comment.is_empty()) {
+        Some(comment) => format!("{}: {}", amount, comment),
+        None => amount,
+    })
+}
+
+/// Amount of a BOLT11 invoice in millisats, from the digits and multiplier after `lnbc`
+fn bolt11_msats(invoice: &str) -> Option<u64> {
+    let invoice = invoice.to_lowercase();
+    // The human-readable part ends at the last `1`, bech32 data never contains one
+    let hrp = &invoice[..invoice.rfind('1')?];
+    let amount = ["lnbcrt", "lntbs", "lntb", "lnbc"].iter()
+        .find_map(|prefix| hrp.strip_prefix(prefix))?;
+    let (digits, multiplier) = match amount.chars().last()? {
+        multiplier if multiplier.is_ascii_alphabetic() => (&amount[..amount.len() - 1], Some(multiplier)),
+        _ => (amount, None),
+    };
+    let amount: u64 = digits.parse().ok()?;
+    // Amounts are in BTC, scaled by the multiplier; 1 BTC is 100,000,000,000 msats
+    match multiplier {
+        None => amount.checked_mul(100_000_000_000),
+        Some('m') => amount.checked_mul(100_000_000),
+        Some('u') => amount.checked_mul(100_000),
+        Some('n') => amount.checked_mul(100),
+        Some('p') => Some(amount / 10),
+        Some(_) => None,
+    }
+}
+
+/// Notifications newest first, saved with their read state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationStore {
+    notifications: Vec<Notification>,
+}
+
+pub fn get_notifications_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("notifications.json"))
+}
+
+impl NotificationStore {
+    pub fn load() -> Result<Self, NostratuiError> {
+        let notifications_path = get_notifications_file()?;
+        if !notifications_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(notifications_path)?)?)
+    }
+
+    pub fn save(&self) -> Result<(), NostratuiError> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to serialize notifications: {}", e)))?;
+        fs::write(get_notifications_file()?, json)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to write notifications: {}", e)))
+    }
+
+    /// Where the subscription should start so nothing since the last session is missed
+    pub fn since(&self) -> Timestamp {
+        // Notifications saved before times were clamped may still be in the future
+        let newest = self.notifications.first().map(|notification| notification.created_at.min(Timestamp::now().as_u64()));
+        match newest {
+            Some(newest) => Timestamp::from_secs(newest.saturating_sub(OVERLAP_SECS)),
+            None => Timestamp::from_secs(Timestamp::now().as_u64().saturating_sub(INITIAL_LOOKBACK_SECS)),
+        }
+    }
+
+    /// Add the events we haven't seen yet, returning how many were added
+    pub fn add_events(&mut self, events: &[Event], me: &PublicKey) -> usize {
+        let mut added = 0;
+        for notification in events.iter().filter_map(|event| Notification::from_event(event, me)) {
+            if self.notifications.iter().any(|known| known.id == notification.id) {
+                continue;
+            }
+            let position = self.notifications.partition_point(|known| known.created_at > notification.created_at);
+            self.notifications.insert(position, notification);
+            added += 1;
+        }
+        self.notifications.truncate(MAX_NOTIFICATIONS);
+        added
+    }
+
+    /// Notifications of one kind, or all of them, newest first
+    pub fn list(&self, kind: Option<NotificationKind>) -> Vec<&Notification> {
+        self.notifications.iter()
+            .filter(|notification| kind.is_none_or(|kind| notification.kind == kind))
+            .collect()
+    }
+
+    pub fn unread(&self, kind: Option<NotificationKind>) -> usize {
+        self.list(kind).iter().filter(|notification| !notification.read).count()
+    }
+
+    /// Mark one notification read, returning whether it was unread
+    pub fn mark_read(&mut self, id: &str) -> bool {
+        match self.notifications.iter_mut().find(|notification| notification.id == id && !notification.read) {
+            Some(notification) => {
+                notification.read = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn mark_all_read(&mut self, kind: Option<NotificationKind>) {
+        for notification in self.notifications.iter_mut().filter(|notification| kind.is_none_or(|kind| notification.kind == kind)) {
+            notification.read = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_support::{forged, sign, tag, NOTE_ID as NOTE};
+
+    fn zap_request_from(zapper: &Keys, me: &Keys, msats: &str) -> Event {
+        sign(Kind::ZapRequest, "great note", vec![
+            tag(&["amount", msats]),
+            tag(&["p", &me.public_key().to_hex()]),
+            tag(&["e", NOTE]),
+        ], zapper)
+    }
+
+    fn receipt(request: &str, invoice: &str, me: &Keys) -> Event {
+        sign(Kind::ZapReceipt, "", vec![
+            tag(&["bolt11", invoice]),
+            tag(&["description", request]),
+            tag(&["p", &me.public_key().to_hex()]),
+            tag(&["e", NOTE]),
+        ], &Keys::generate())
+    }
+
+    #[test]
+    fn bolt11_multipliers() {
+        assert_eq!(bolt11_msats("lnbc21pvjluezpp5"), Some(200_000_000_000));
+        assert_eq!(bolt11_msats("lnbc20m1pvjluezpp5"), Some(2_000_000_000));
+        assert_eq!(bolt11_msats("lnbc2500u1pvjluezpp5"), Some(250_000_000));
+        assert_eq!(bolt11_msats("lnbc210n1pvjluezpp5"), Some(21_000));
+        assert_eq!(bolt11_msats("lnbc10p1pvjluezpp5"), Some(1));
+        assert_eq!(bolt11_msats("LNBC210N1PVJLUEZPP5"), Some(21_000));
+    }
+
+    #[test]
+    fn bolt11_network_prefixes() {
+        assert_eq!(bolt11_msats("lnbcrt500u1pvjluezpp5"), Some(50_000_000));
+        assert_eq!(bolt11_msats("lntbs15n1pvjluezpp5"), Some(1_500));
+        assert_eq!(bolt11_msats("lntb1u1pvjluezpp5"), Some(100_000));
+    }
+
+    #[test]
+    fn bolt11_without_an_amount() {
+        assert_eq!(bolt11_msats("lnbc1pvjluezpp5"), None);
+        assert_eq!(bolt11_msats("lnbc25x1pvjluezpp5"), None);
+        assert_eq!(bolt11_msats("lnurl1dp68gurn8ghj7"), None);
+        assert_eq!(bolt11_msats("lnbc"), None);
+    }
+
+    #[test]
+    fn notes_are_replies_or_mentions() {
+        let me = Keys::generate();
+        let sender = Keys::generate();
+        let p = tag(&["p", &me.public_key().to_hex()]);
+
+        let reply = sign(Kind::TextNote, "yes", vec![tag(&["e", NOTE, "", "root"]), p.clone()], &sender);
+        let notification = Notification::from_event(&reply, &me.public_key()).unwrap();
+        assert_eq!(notification.kind, NotificationKind::Reply);
+        assert_eq!(notification.author, sender.public_key().to_hex());
+        assert_eq!(notification.post.unwrap().content, "yes");
+        assert_eq!(notification.target, None);
+
+        let mention = sign(Kind::TextNote, "hey", vec![p], &sender);
+        assert_eq!(Notification::from_event(&mention, &me.public_key()).unwrap().kind, NotificationKind::Mention);
+    }
+
+    #[test]
+    fn reactions_and_reposts_point_at_our_note() {
+        let me = Keys::generate();
+        let sender = Keys::generate();
+        let tags = vec![tag(&["e", NOTE]), tag(&["p", &me.public_key().to_hex()])];
+
+        let reaction = Notification::from_event(&sign(Kind::Reaction, "🔥", tags.clone(), &sender), &me.public_key()).unwrap();
+        assert_eq!(reaction.kind, NotificationKind::Reaction);
+        assert_eq!(reaction.content, "🔥");
+        assert_eq!(reaction.target.as_deref(), Some(NOTE));
+
+        let repost = Notification::from_event(&sign(Kind::Repost, "", tags, &sender), &me.public_key()).unwrap();
+        assert_eq!(repost.kind, NotificationKind::Repost);
+        assert_eq!(repost.target.as_deref(), Some(NOTE));
+    }
+
+    #[test]
+    fn our_own_events_and_other_kinds_are_skipped() {
+        let me = Keys::generate();
+        let own = sign(Kind::TextNote, "me", vec![tag(&["p", &me.public_key().to_hex()])], &me);
+        assert!(Notification::from_event(&own, &me.public_key()).is_none());
+        let metadata = sign(Kind::Metadata, "{}", Vec::new(), &Keys::generate());
+        assert!(Notification::from_event(&metadata, &me.public_key()).is_none());
+    }
+
+    #[test]
+    fn future_timestamps_are_clamped() {
+        let me = Keys::generate();
+        let ahead = Timestamp::from_secs(Timestamp::now().as_u64() + 3600);
+        let note = EventBuilder::text_note("from the future")
+            .tag(tag(&["p", &me.public_key().to_hex()]))
+            .custom_created_at(ahead)
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+        let notification = Notification::from_event(&note, &me.public_key()).unwrap();
+        assert!(notification.created_at <= Timestamp::now().as_u64());
+    }
+
+    #[test]
+    fn out_of_range_timestamps_do_not_panic() {
+        let me = Keys::generate();
+        for secs in [i64::MAX as u64, u64::MAX] {
+            let note = EventBuilder::text_note("far off")
+                .tag(tag(&["p", &me.public_key().to_hex()]))
+                .custom_created_at(Timestamp::from_secs(secs))
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            let notification = Notification::from_event(&note, &me.public_key()).unwrap();
+            assert_eq!(notification.post.unwrap().datetime, "");
+        }
+    }
+
+    #[test]
+    fn zap_is_from_the_zapper_for_the_invoiced_amount() {
+        let me = Keys::generate();
+        let zapper = Keys::generate();
+        let request = zap_request_from(&zapper, &me, "21000");
+        let zap = Notification::from_event(&receipt(&request.as_json(), "lnbc210n1pvjluezpp5", &me), &me.public_key()).unwrap();
+        assert_eq!(zap.kind, NotificationKind::Zap);
+        assert_eq!(zap.author, zapper.public_key().to_hex());
+        assert_eq!(zap.content, "21 sats: great note");
+        assert_eq!(zap.target.as_deref(), Some(NOTE));
+    }
+
+    #[test]
+    fn zap_with_mismatched_amounts_is_dropped() {
+        let me = Keys::generate();
+        let request = zap_request_from(&Keys::generate(), &me, "21000000");
+        assert!(Notification::from_event(&receipt(&request.as_json(), "lnbc210n1pvjluezpp5", &me), &me.public_key()).is_none());
+    }
+
+    #[test]
+    fn zap_with_a_forged_or_missing_request_is_dropped() {
+        let me = Keys::generate();
+        let request = zap_request_from(&Keys::generate(), &me, "21000");
+        assert!(Notification::from_event(&receipt(&forged(&request, "forged comment"), "lnbc210n1pvjluezpp5", &me), &me.public_key()).is_none());
+
+        let not_a_request = sign(Kind::TextNote, "hi", Vec::new(), &Keys::generate());
+        assert!(Notification::from_event(&receipt(&not_a_request.as_json(), "lnbc210n1pvjluezpp5", &me), &me.public_key()).is_none());
+        assert!(Notification::from_event(&receipt("", "lnbc210n1pvjluezpp5", &me), &me.public_key()).is_none());
+    }
+
+    #[test]
+    fn zapping_ourselves_is_skipped() {
+        let me = Keys::generate();
+        let request = zap_request_from(&me, &me, "21000");
+        assert!(Notification::from_event(&receipt(&request.as_json(), "lnbc210n1pvjluezpp5", &me), &me.public_key()).is_none());
+    }
+}
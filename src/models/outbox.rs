@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::NostrClient;
+use crate::models::cache::get_cache_dir;
+use crate::error::NostratuiError;
+
+/// Automatic retries before an entry is parked as failed
+const MAX_ATTEMPTS: u32 = 8;
+const BASE_BACKOFF_SECS: u64 = 30;
+const MAX_BACKOFF_SECS: u64 = 60 * 60;
+
+// Serializes read-modify-write cycles between the UI and the retry task
+static OUTBOX_LOCK: Mutex<()> = Mutex::new(());
+// Set while a flush is publishing, so the retry task and a manual retry never send an entry twice
+static FLUSHING: AtomicBool = AtomicBool::new(false);
+
+// Clears FLUSHING however the flush ends
+struct FlushGuard;
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        FLUSHING.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutboxState {
+    /// Waiting for the next automatic retry
+    Pending,
+    /// Gave up after [`MAX_ATTEMPTS`], only retried on request
+    Failed,
+}
+
+/// A signed event that no relay has accepted yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub event: Event,
+    pub state: OutboxState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_retry: u64,
+}
+
+impl OutboxEntry {
+    fn new(event: Event, error: String) -> Self {
+        let mut entry = Self {
+            event,
+            state: OutboxState::Pending,
+            attempts: 0,
+            last_error: None,
+            next_retry: 0,
+        };
+        entry.record_failure(error);
+        entry
+    }
+
+    fn record_failure(&mut self, error: String) {
+        self.attempts += 1;
+        self.last_error = Some(error);
+        if self.attempts >= MAX_ATTEMPTS {
+            self.state = OutboxState::Failed;
+        } else {
+            let backoff = BASE_BACKOFF_SECS
+                .saturating_mul(1 << (self.attempts - 1))
+                .min(MAX_BACKOFF_SECS);
+            self.next_retry = Timestamp::now().as_u64() + backoff;
+        }
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        self.state == OutboxState::Pending && self.next_retry <= now
+    }
+}
+
+pub fn get_outbox_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("outbox.json"))
+}
+
+pub fn load_outbox() -> Result<Vec<OutboxEntry>, NostratuiError> {
+    let outbox_path = get_outbox_file()?;
+    if !outbox_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let outbox_data = fs::read_to_string(outbox_path)?;
+    Ok(serde_json::from_str(&outbox_data)?)
+}
+
+fn save_outbox(entries: &[OutboxEntry]) -> Result<(), NostratuiError> {
+    let json = serde_json::to_string_pretty(entries)
+        .map_err(|e| NostratuiError::Cache(format!("Failed to serialize outbox: {}", e)))?;
+
+    fs::write(get_outbox_file()?, json)
+        .map_err(|e| NostratuiError::Cache(format!("Failed to write outbox file: {}", e)))
+}
+
+fn update_outbox<F>(f: F) -> Result<(), NostratuiError>
+where
+    F: FnOnce(&mut Vec<OutboxEntry>),
+{
+    let _guard = OUTBOX_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut entries = load_outbox()?;
+    f(&mut entries);
+    save_outbox(&entries)
+}
+
+/// Keep a signed event that failed to publish so it can be retried later
+pub fn queue_event(event: Event, error: String) -> Result<(), NostratuiError> {
+    update_outbox(|entries| {
+        match entries.iter_mut().find(|entry| entry.event.id == event.id) {
+            Some(entry) => entry.record_failure(error),
+            None => entries.push(OutboxEntry::new(event, error)),
+        }
+    })
+}
+
+pub fn discard(event_id: &EventId) -> Result<(), NostratuiError> {
+    update_outbox(|entries| entries.retain(|entry| entry.event.id != *event_id))
+}
+
+/// Make an entry due immediately, reviving it if it had been marked failed
+pub fn reschedule(event_id: &EventId) -> Result<(), NostratuiError> {
+    update_outbox(|entries| {
+        if let Some(entry) = entries.iter_mut().find(|entry| entry.event.id == *event_id) {
+            entry.state = OutboxState::Pending;
+            entry.attempts = entry.attempts.min(MAX_ATTEMPTS - 1);
+            entry.next_retry = 0;
+        }
+    })
+}
+
+/// Try to publish every due entry, returning how many were accepted.
+///
+/// The lock is not held while publishing, so entries discarded in the
+/// meantime are not resurrected. Only one flush runs at a time, another
+/// started meanwhile returns 0 and leaves due entries to the next one.
+pub async fn flush(client: &NostrClient) -> Result<usize, NostratuiError> {
+    if FLUSHING.swap(true, Ordering::AcqRel) {
+        return Ok(0);
+    }
+    let _guard = FlushGuard;
+    let now = Timestamp::now().as_u64();
+    let due: Vec<Event> = load_outbox()?
+        .into_iter()
+        .filter(|entry| entry.is_due(now))
+        .map(|entry| entry.event)
+        .collect();
+
+    let mut results = Vec::new();
+    for event in due {
+        let result = client.publish_event(&event).await;
+        results.push((event.id, result));
+    }
+
+    let published = results.iter().filter(|(_, result)| result.is_ok()).count();
+    update_outbox(|entries| {
+        for (event_id, result) in results {
+            match result {
+                Ok(_) => entries.retain(|entry| entry.event.id != event_id),
+                Err(e) => {
+                    if let Some(entry) = entries.iter_mut().find(|entry| entry.event.id == event_id) {
+                        entry.record_failure(e.to_string());
+                    }
+                }
+            }
+        }
+    })?;
+
+    Ok(published)
+}
@@ -1,8 +1,13 @@
+use chrono::{DateTime, Local, Utc, TimeZone};
+use nostr_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::models::nip10::{last_event_tag, ThreadRefs};
+use crate::models::nip18;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Post {
-    pub user: String,
+    /// Hex key of the author, names are looked up in a `ProfileStore` when rendering
+    pub pubkey: String,
     pub timestamp: u64,
     pub datetime: String,
     pub content: String,
@@ -13,9 +18,76 @@ pub struct Post {
     pub mentions: Vec<String>,
     #[serde(default)]
     pub participants: Vec<String>,
+    /// For reposts, the id of the reposted note
+    #[serde(default)]
+    pub reposted_id: Option<String>,
+    /// For reposts, the reposted note once it's known, embedded or fetched separately
+    #[serde(default)]
+    pub repost: Option<Box<Post>>,
 }
 
 impl Post {
+    pub fn from_event(event: &Event) -> Self {
+        // Anyone can sign any created_at, one chrono can't represent is shown without a time
+        let datetime = i64::try_from(event.created_at.as_u64()).ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .map(|utc_time| DateTime::<Local>::from(utc_time).format("%H:%M %h-%d-%Y").to_string())
+            .unwrap_or_default();
+
+        if event.kind == Kind::Repost {
+            return Self::from_repost(event, datetime);
+        }
+
+        let refs = ThreadRefs::from_event(event);
+
+        Post {
+            pubkey: event.pubkey.to_hex(),
+            timestamp: event.created_at.as_u64(),
+            datetime,
+            content: event.content.to_string(),
+            id: event.id.to_hex(),
+            root_id: refs.root.map(|root| root.id),
+            reply_id: refs.reply.map(|reply| reply.id),
+            mentions: refs.mentions.into_iter().map(|mention| mention.id).collect(),
+            participants: refs.participants,
+            reposted_id: None,
+            repost: None,
+        }
+    }
+
+    // The repost's own tags point at the original rather than at a thread, so they are left out
+    fn from_repost(event: &Event, datetime: String) -> Self {
+        let original = nip18::embedded_note(event).map(|original| Box::new(Post::from_event(&original)));
+        Post {
+            pubkey: event.pubkey.to_hex(),
+            timestamp: event.created_at.as_u64(),
+            datetime,
+            content: original.as_ref().map(|original| original.content.clone()).unwrap_or_default(),
+            id: event.id.to_hex(),
+            root_id: None,
+            reply_id: None,
+            mentions: Vec::new(),
+            participants: Vec::new(),
+            reposted_id: last_event_tag(event).or_else(|| original.as_ref().map(|original| original.id.clone())),
+            repost: original,
+        }
+    }
+
+    /// Fill in the reposted note when it wasn't embedded in the repost
+    pub fn set_repost(&mut self, original: Post) {
+        self.content = original.content.clone();
+        self.repost = Some(Box::new(original));
+    }
+
+    pub fn is_repost(&self) -> bool {
+        self.reposted_id.is_some()
+    }
+
+    /// What replies, reactions and reposts act on: the reposted note for a repost, otherwise the post itself
+    pub fn target(&self) -> &Post {
+        self.repost.as_deref().unwrap_or(self)
+    }
+
     pub fn is_reply(&self) -> bool {
         self.reply_id.is_some()
     }
@@ -24,7 +96,8 @@ impl Post {
         self.root_id.is_none() && self.reply_id.is_none()
     }
 
+    /// Whether this replies to a reply rather than directly to the root
     pub fn is_thread_reply(&self) -> bool {
-        self.root_id.is_some() && self.reply_id.is_some()
+        self.root_id.is_some() && self.reply_id.is_some() && self.root_id != self.reply_id
     }
 }
@@ -0,0 +1,177 @@
+//! NIP-13 proof of work: mining notes on a blocking worker and checking incoming ones.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use nostr_sdk::prelude::*;
+use nostr_sdk::nips::nip13::get_leading_zero_bits;
+
+/// Work put into notes unless configured otherwise, what notes always had before
+pub const DEFAULT_POW_DIFFICULTY: u8 = 20;
+/// Hashes between checks for cancellation and progress updates
+const MINING_BATCH: u64 = 4096;
+
+/// How much work to put into published notes
+#[derive(Debug, Clone, Default)]
+pub struct PowSettings {
+    /// Leading zero bits for every note, 0 disables mining
+    pub difficulty: u8,
+    /// Difficulty wanted by individual relays
+    pub relay_difficulty: HashMap<String, u8>,
+    /// Re-mine for relays that reject a note for too little work, and remember what they asked for
+    pub match_relay: bool,
+}
+
+/// A note being mined, shared with whoever shows its progress or may cancel it
+#[derive(Debug)]
+pub struct MiningJob {
+    pub difficulty: u8,
+    started: Instant,
+    attempts: AtomicU64,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+}
+
+impl MiningJob {
+    pub fn new(difficulty: u8) -> Arc<Self> {
+        Arc::new(Self {
+            difficulty,
+            started: Instant::now(),
+            attempts: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        })
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Expected time left at the current hash rate, None until there is a rate to go by.
+    ///
+    /// Each hash is an independent try, so this is the expected total minus the time spent,
+    /// and a lucky or unlucky run can finish well before or after it.
+    pub fn eta(&self) -> Option<Duration> {
+        let elapsed = self.elapsed().as_secs_f64();
+        let attempts = self.attempts();
+        if attempts == 0 || elapsed <= 0.0 {
+            return None;
+        }
+        let rate = attempts as f64 / elapsed;
+        let expected = 2f64.powi(self.difficulty as i32) / rate;
+        Some(Duration::from_secs_f64((expected - elapsed).max(0.0)))
+    }
+}
+
+/// Add a nonce tag to `unsigned` until its id has `job.difficulty` leading zero bits.
+///
+/// This spins the CPU, run it on a blocking worker. Returns None once the job is cancelled.
+pub fn mine(mut unsigned: UnsignedEvent, job: &MiningJob) -> Option<UnsignedEvent> {
+    let difficulty = job.difficulty;
+    let base: Vec<Tag> = unsigned.tags.iter()
+        .filter(|tag| tag.kind() != TagKind::Nonce)
+        .cloned()
+        .collect();
+
+    let mut nonce: u128 = 0;
+    let found = loop {
+        if job.is_cancelled() {
+            job.finished.store(true, Ordering::Relaxed);
+            return None;
+        }
+        let mut tags = None;
+        for _ in 0..MINING_BATCH {
+            let mut candidate = base.clone();
+            if difficulty > 0 {
+                candidate.push(Tag::pow(nonce, difficulty));
+            }
+            let candidate = Tags::from_list(candidate);
+            let id = EventId::new(&unsigned.pubkey, &unsigned.created_at, &unsigned.kind, &candidate, &unsigned.content);
+            nonce += 1;
+            if get_leading_zero_bits(id.as_bytes()) >= difficulty {
+                tags = Some((candidate, id));
+                break;
+            }
+        }
+        job.attempts.fetch_add(MINING_BATCH, Ordering::Relaxed);
+        if let Some(found) = tags {
+            break found;
+        }
+    };
+
+    unsigned.tags = found.0;
+    unsigned.id = Some(found.1);
+    job.finished.store(true, Ordering::Relaxed);
+    Some(unsigned)
+}
+
+/// Work in a note, counted as leading zero bits of its id
+pub fn difficulty_of(id: &str) -> u8 {
+    EventId::from_hex(id)
+        .map(|id| get_leading_zero_bits(id.as_bytes()))
+        .unwrap_or(0)
+}
+
+/// Difficulty a relay asked for in a `pow:` rejection, taken as the largest number in it
+pub fn required_by_rejection(reason: &str) -> Option<u8> {
+    let detail = reason.trim().strip_prefix("pow:")?;
+    detail.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|number| number.parse::<u8>().ok())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejection_takes_the_largest_number() {
+        assert_eq!(required_by_rejection("pow: difficulty 20 is less than 28"), Some(28));
+        assert_eq!(required_by_rejection("pow: required 30, got 12"), Some(30));
+        assert_eq!(required_by_rejection("  pow: 16"), Some(16));
+    }
+
+    #[test]
+    fn rejection_without_pow_or_a_number() {
+        assert_eq!(required_by_rejection("blocked: not on the allow list 28"), None);
+        assert_eq!(required_by_rejection("pow: not enough work"), None);
+    }
+
+    #[test]
+    fn mined_note_has_the_difficulty() {
+        let keys = Keys::generate();
+        let unsigned = EventBuilder::text_note("hello").build(keys.public_key());
+        let job = MiningJob::new(8);
+        let mined = mine(unsigned, &job).unwrap();
+        assert!(job.is_finished());
+        assert!(difficulty_of(&mined.id.unwrap().to_hex()) >= 8);
+        assert_eq!(mined.tags.iter().filter(|tag| tag.kind() == TagKind::Nonce).count(), 1);
+    }
+
+    #[test]
+    fn cancelled_job_stops_mining() {
+        let keys = Keys::generate();
+        let unsigned = EventBuilder::text_note("hello").build(keys.public_key());
+        let job = MiningJob::new(64);
+        job.cancel();
+        assert!(mine(unsigned, &job).is_none());
+        assert!(job.is_finished());
+    }
+}
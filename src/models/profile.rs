@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Post;
+use crate::models::cache::get_cache_dir;
+use crate::error::NostratuiError;
+
+/// How long fetched metadata is trusted before it is requested again
+pub const DEFAULT_PROFILE_TTL: u64 = 60 * 60 * 24;
+
+/// Kind 0 metadata of an author
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    pub display_name: Option<String>,
+    pub name: Option<String>,
+    pub about: Option<String>,
+    pub picture: Option<String>,
+    pub nip05: Option<String>,
+    pub lud16: Option<String>,
+    pub website: Option<String>,
+    /// `created_at` of the metadata event, so older events never replace newer ones
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+impl Profile {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let value = serde_json::from_str::<serde_json::Value>(&event.content).ok()?;
+        let field = |key: &str| value.get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
+
+        Some(Self {
+            // Some clients still write the older camel case key
+            display_name: field("display_name").or_else(|| field("displayName")),
+            name: field("name"),
+            about: field("about"),
+            picture: field("picture"),
+            nip05: field("nip05"),
+            lud16: field("lud16"),
+            website: field("website"),
+            updated_at: event.created_at.as_u64(),
+        })
+    }
+}
+
+/// Persisted part of the store, keyed by hex pubkey
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProfileFile {
+    profiles: HashMap<String, Profile>,
+    /// When metadata was last requested, including authors that have none
+    checked: HashMap<String, u64>,
+}
+
+/// Author metadata, looked up whenever a post is rendered
+#[derive(Debug, Clone, Default)]
+pub struct ProfileStore {
+    profiles: HashMap<PublicKey, Profile>,
+    checked: HashMap<PublicKey, u64>,
+    petnames: HashMap<PublicKey, String>,
+}
+
+pub fn get_profiles_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("profiles.json"))
+}
+
+impl ProfileStore {
+    /// Start from the names given to contacts in the config, which win over metadata
+    pub fn with_contacts(contacts: &[(String, String)]) -> Self {
+        let petnames = contacts.iter()
+            .filter_map(|(key, name)| Some((PublicKey::parse(key).ok()?, name.clone())))
+            .collect();
+        Self {
+            petnames,
+            ..Self::default()
+        }
+    }
+
+    /// Load the persisted metadata on top of the contacts' names
+    pub fn load(contacts: &[(String, String)]) -> Result<Self, NostratuiError> {
+        let mut store = Self::with_contacts(contacts);
+        let profiles_path = get_profiles_file()?;
+        if !profiles_path.exists() {
+            return Ok(store);
+        }
+
+        let file: ProfileFile = serde_json::from_str(&fs::read_to_string(profiles_path)?)?;
+        for (key, profile) in file.profiles {
+            if let Ok(public_key) = PublicKey::from_hex(&key) {
+                store.profiles.insert(public_key, profile);
+            }
+        }
+        for (key, checked_at) in file.checked {
+            if let Ok(public_key) = PublicKey::from_hex(&key) {
+                store.checked.insert(public_key, checked_at);
+            }
+        }
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<(), NostratuiError> {
+        let file = ProfileFile {
+            profiles: self.profiles.iter().map(|(pk, p)| (pk.to_hex(), p.clone())).collect(),
+            checked: self.checked.iter().map(|(pk, t)| (pk.to_hex(), *t)).collect(),
+        };
+        let json = serde_json::to_string(&file)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to serialize profiles: {}", e)))?;
+        fs::write(get_profiles_file()?, json)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to write profiles file: {}", e)))
+    }
+
+    pub fn insert(&mut self, public_key: PublicKey, profile: Profile) {
+        match self.profiles.get(&public_key) {
+            Some(existing) if existing.updated_at > profile.updated_at => {},
+            _ => {
+                self.profiles.insert(public_key, profile);
+            }
+        }
+    }
+
+    /// Record that metadata for `authors` was requested at `now`, found or not
+    pub fn mark_checked(&mut self, authors: &[PublicKey], now: u64) {
+        for public_key in authors {
+            self.checked.insert(*public_key, now);
+        }
+    }
+
+    /// Take in profiles and check times from another store, keeping the newest of each
+    pub fn merge(&mut self, other: ProfileStore) {
+        for (public_key, profile) in other.profiles {
+            self.insert(public_key, profile);
+        }
+        for (public_key, checked_at) in other.checked {
+            let entry = self.checked.entry(public_key).or_default();
+            *entry = (*entry).max(checked_at);
+        }
+    }
+
+    /// Authors never requested or last requested more than `ttl` seconds ago, without duplicates
+    pub fn stale<'a, I>(&self, authors: I, ttl: u64) -> Vec<PublicKey>
+    where
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        let now = Timestamp::now().as_u64();
+        let mut stale: Vec<PublicKey> = authors.into_iter()
+            .filter(|pk| self.checked.get(pk).is_none_or(|checked_at| checked_at + ttl < now))
+            .copied()
+            .collect();
+        stale.sort();
+        stale.dedup();
+        stale
+    }
+
+    /// Whether the author has a name better than their npub
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.petnames.contains_key(public_key) || self.profiles.contains_key(public_key)
+    }
+
+    pub fn get(&self, public_key: &PublicKey) -> Option<&Profile> {
+        self.profiles.get(public_key)
+    }
+
+    /// Petname, display name, name or NIP-05, falling back to a shortened npub.
+    ///
+    /// Posts cached before pubkeys were stored carry the old display name
+    /// instead of a key, which is shown unchanged.
+    pub fn display_name(&self, pubkey: &str) -> String {
+        match PublicKey::parse(pubkey) {
+            Ok(public_key) => self.display_name_for(&public_key),
+            Err(_) => pubkey.to_string(),
+        }
+    }
+
+    /// Who a post is from: the author's name, or `alice reposted bob` for a repost
+    pub fn byline(&self, post: &Post) -> String {
+        let name = self.display_name(&post.pubkey);
+        match (post.is_repost(), &post.repost) {
+            (false, _) => name,
+            (true, Some(original)) => format!("{} reposted {}", name, self.display_name(&original.pubkey)),
+            (true, None) => format!("{} reposted", name),
+        }
+    }
+
+    pub fn display_name_for(&self, public_key: &PublicKey) -> String {
+        if let Some(petname) = self.petnames.get(public_key) {
+            return petname.clone();
+        }
+        self.profiles.get(public_key)
+            .and_then(|p| p.display_name.clone().or_else(|| p.name.clone()).or_else(|| p.nip05.clone()))
+            .unwrap_or_else(|| short_npub(public_key))
+    }
+}
+
+/// `npub1abcdefgh…wxyz`, enough to tell keys apart without the full width
+pub fn short_npub(public_key: &PublicKey) -> String {
+    match public_key.to_bech32() {
+        Ok(npub) => format!("{}…{}", &npub[..12], &npub[npub.len() - 4..]),
+        Err(_) => public_key.to_hex()[..12].to_string(),
+    }
+}
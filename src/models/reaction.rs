@@ -0,0 +1,169 @@
+//! NIP-25 reactions: tallying kind 7 events per note and tagging our own.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use nostr_sdk::prelude::*;
+use crate::models::Post;
+use crate::models::nip10::last_event_tag;
+use crate::error::NostratuiError;
+
+/// What the reaction picker offers, likes and dislikes first
+pub const REACTION_CHOICES: [&str; 8] = ["+", "-", "🤙", "❤️", "😂", "🔥", "👀", "🙏"];
+
+/// Reactions to one note
+#[derive(Debug, Clone, Default)]
+pub struct ReactionCounts {
+    pub likes: usize,
+    pub dislikes: usize,
+    /// Emoji and `:shortcode:` reactions with how often each was used
+    pub emoji: BTreeMap<String, usize>,
+    /// Our own reaction, if we reacted
+    pub mine: Option<String>,
+}
+
+impl ReactionCounts {
+    pub fn is_empty(&self) -> bool {
+        self.likes == 0 && self.dislikes == 0 && self.emoji.is_empty()
+    }
+
+    /// `+3 -1 🤙2`, most used emoji first
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.likes > 0 {
+            parts.push(format!("+{}", self.likes));
+        }
+        if self.dislikes > 0 {
+            parts.push(format!("-{}", self.dislikes));
+        }
+        let mut emoji: Vec<(&String, &usize)> = self.emoji.iter().collect();
+        emoji.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+        parts.extend(emoji.into_iter().map(|(emoji, count)| format!("{}{}", emoji, count)));
+        parts.join(" ")
+    }
+}
+
+/// Reaction counts for the notes we've asked about, counting each key's latest reaction to a note once
+#[derive(Debug, Default)]
+pub struct ReactionStore {
+    /// Newest reaction per note and reacting key, by `created_at` then id so every copy agrees
+    latest: HashMap<String, HashMap<PublicKey, (Timestamp, EventId, String)>>,
+    counts: HashMap<String, ReactionCounts>,
+    seen: HashSet<EventId>,
+}
+
+impl ReactionStore {
+    pub fn add_events(&mut self, events: impl IntoIterator<Item = Event>, me: &PublicKey) {
+        let mut changed = HashSet::new();
+        for event in events {
+            if event.kind != Kind::Reaction || !self.seen.insert(event.id) {
+                continue;
+            }
+            // NIP-25 puts the note reacted to in the last `e` tag
+            let Some(target) = last_event_tag(&event) else {
+                continue;
+            };
+            let reaction = (event.created_at, event.id, event.content.trim().to_string());
+            let latest = self.latest.entry(target.clone()).or_default();
+            match latest.get(&event.pubkey) {
+                Some((at, id, _)) if (*at, *id) >= (reaction.0, reaction.1) => continue,
+                _ => {
+                    latest.insert(event.pubkey, reaction);
+                    changed.insert(target);
+                },
+            }
+        }
+
+        for target in changed {
+            let counts = tally(&self.latest[&target], me);
+            self.counts.insert(target, counts);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ReactionCounts> {
+        self.counts.get(id)
+    }
+}
+
+fn tally(latest: &HashMap<PublicKey, (Timestamp, EventId, String)>, me: &PublicKey) -> ReactionCounts {
+    let mut counts = ReactionCounts::default();
+    for (pubkey, (_, _, content)) in latest {
+        match content.as_str() {
+            "" | "+" => counts.likes += 1,
+            "-" => counts.dislikes += 1,
+            emoji => *counts.emoji.entry(emoji.to_string()).or_default() += 1,
+        }
+        if pubkey == me {
+            counts.mine = Some(content.clone());
+        }
+    }
+    counts
+}
+
+/// `e`, `p` and `k` tags for reacting to `post`
+pub fn reaction_tags(post: &Post, relay: Option<String>) -> Result<Vec<Tag>, NostratuiError> {
+    let parse = |values: Vec<String>| Tag::parse(values).map_err(|e| NostratuiError::NostrSdk(e.to_string()));
+    Ok(vec![
+        parse(vec!["e".to_string(), post.id.clone(), relay.unwrap_or_default(), post.pubkey.clone()])?,
+        parse(vec!["p".to_string(), post.pubkey.clone()])?,
+        parse(vec!["k".to_string(), Kind::TextNote.as_u16().to_string()])?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_support::{tag, NOTE_ID as NOTE};
+
+    fn reaction(keys: &Keys, content: &str) -> Event {
+        reaction_at(keys, content, Timestamp::now().as_u64())
+    }
+
+    fn reaction_at(keys: &Keys, content: &str, created_at: u64) -> Event {
+        EventBuilder::new(Kind::Reaction, content)
+            .tag(tag(&["e", NOTE]))
+            .custom_created_at(Timestamp::from_secs(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn counts_each_kind_of_reaction() {
+        let me = Keys::generate();
+        let mut store = ReactionStore::default();
+        store.add_events([
+            reaction(&Keys::generate(), "+"),
+            reaction(&Keys::generate(), ""),
+            reaction(&Keys::generate(), "-"),
+            reaction(&Keys::generate(), "🔥"),
+        ], &me.public_key());
+        let counts = store.get(NOTE).unwrap();
+        assert_eq!((counts.likes, counts.dislikes), (2, 1));
+        assert_eq!(counts.emoji.get("🔥"), Some(&1));
+        assert_eq!(counts.mine, None);
+    }
+
+    #[test]
+    fn each_key_counts_once_with_its_latest_reaction() {
+        let me = Keys::generate();
+        let other = Keys::generate();
+        let first = reaction_at(&me, "+", 100);
+        let mut store = ReactionStore::default();
+        store.add_events([
+            first.clone(),
+            first,
+            reaction_at(&me, "-", 200),
+            reaction_at(&other, "🔥", 300),
+            reaction_at(&other, "🔥", 301),
+            reaction_at(&other, "+", 50),
+        ], &me.public_key());
+        let counts = store.get(NOTE).unwrap();
+        assert_eq!((counts.likes, counts.dislikes), (0, 1));
+        assert_eq!(counts.emoji.get("🔥"), Some(&1));
+        assert_eq!(counts.mine.as_deref(), Some("-"));
+
+        // A newer one arriving later replaces the earlier count
+        store.add_events([reaction_at(&other, "+", 400)], &me.public_key());
+        let counts = store.get(NOTE).unwrap();
+        assert_eq!((counts.likes, counts.dislikes), (1, 1));
+        assert!(counts.emoji.is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+/// NOTICE and CLOSED messages kept per relay
+const MAX_RELAY_MESSAGES: usize = 20;
+
+/// A NOTICE or CLOSED message from a relay
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayMessageEntry {
+    pub at: u64,
+    /// `NOTICE` or `CLOSED`
+    pub kind: String,
+    pub message: String,
+}
+
+/// What we know about one relay's connection
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RelayHealth {
+    pub url: String,
+    /// Connection state as reported by the relay pool, `Not added` if it never was
+    pub state: String,
+    /// Round trip of the last probe, or the pool's ping latency
+    pub latency_ms: Option<u64>,
+    pub last_error: Option<String>,
+    /// When `last_error` happened, errors from before the current connection are dropped
+    #[serde(skip)]
+    last_error_at: u64,
+    pub events_received: u64,
+    /// Oldest first
+    pub messages: VecDeque<RelayMessageEntry>,
+}
+
+impl RelayHealth {
+    fn new(url: &str) -> Self {
+        Self { url: url.to_string(), state: String::from("Not added"), ..Self::default() }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == RelayStatus::Connected.to_string()
+    }
+
+    fn set_error(&mut self, error: String) {
+        self.last_error = Some(error);
+        self.last_error_at = Timestamp::now().as_u64();
+    }
+
+    fn push_message(&mut self, kind: &str, message: String) {
+        self.messages.push_back(RelayMessageEntry {
+            at: Timestamp::now().as_u64(),
+            kind: kind.to_string(),
+            message,
+        });
+        while self.messages.len() > MAX_RELAY_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+}
+
+/// Relay health shared between the client and the task watching pool notifications
+#[derive(Debug, Default)]
+pub struct RelayMonitor {
+    relays: Mutex<HashMap<String, RelayHealth>>,
+}
+
+impl RelayMonitor {
+    fn update(&self, relay: &str, f: impl FnOnce(&mut RelayHealth)) {
+        let mut relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let key = normalize(relay);
+        let health = relays.entry(key.clone()).or_insert_with(|| RelayHealth::new(&key));
+        f(health);
+    }
+
+    /// Count events and keep NOTICE/CLOSED messages as they arrive from the pool
+    pub fn record_notification(&self, notification: &RelayPoolNotification) {
+        let RelayPoolNotification::Message { relay_url, message } = notification else {
+            return;
+        };
+        match message {
+            RelayMessage::Event { .. } => self.update(relay_url.as_str(), |health| health.events_received += 1),
+            RelayMessage::Notice(notice) => {
+                self.update(relay_url.as_str(), |health| health.push_message("NOTICE", notice.to_string()));
+            },
+            RelayMessage::Closed { message, .. } => {
+                self.update(relay_url.as_str(), |health| {
+                    health.push_message("CLOSED", message.to_string());
+                    if !message.is_empty() {
+                        health.set_error(message.to_string());
+                    }
+                });
+            },
+            _ => {},
+        }
+    }
+
+    pub fn record_error(&self, relay: &str, error: impl Into<String>) {
+        let error = error.into();
+        self.update(relay, |health| health.set_error(error));
+    }
+
+    pub fn record_latency(&self, relay: &str, latency: Duration) {
+        self.update(relay, |health| health.latency_ms = Some(latency.as_millis() as u64));
+    }
+
+    /// NOTICE messages `relay` sent at or after `since`
+    pub fn notices_since(&self, relay: &str, since: u64) -> Vec<String> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.get(&normalize(relay))
+            .map(|health| health.messages.iter()
+                .filter(|entry| entry.kind == "NOTICE" && entry.at >= since)
+                .map(|entry| entry.message.clone())
+                .collect())
+            .unwrap_or_default()
+    }
+
+    /// Health of each relay in `urls`, merged with the pool's live state.
+    ///
+    /// A relay that reconnected since its last error has that error cleared.
+    pub fn snapshot(&self, urls: &[String], pool: &HashMap<RelayUrl, Relay>) -> Vec<RelayHealth> {
+        let mut relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        urls.iter()
+            .map(|url| {
+                let key = normalize(url);
+                let mut health = relays.get(&key).cloned().unwrap_or_else(|| RelayHealth::new(&key));
+                if let Some(relay) = pool.iter().find(|(relay_url, _)| normalize(relay_url.as_str()) == key).map(|(_, relay)| relay) {
+                    health.state = relay.status().to_string();
+                    let connected_at = relay.stats().connected_at().as_u64();
+                    if health.is_connected() && health.last_error.is_some() && health.last_error_at < connected_at {
+                        health.last_error = None;
+                        if let Some(stored) = relays.get_mut(&key) {
+                            stored.last_error = None;
+                        }
+                    }
+                    if health.latency_ms.is_none() {
+                        health.latency_ms = relay.stats().latency().map(|latency| latency.as_millis() as u64);
+                    }
+                }
+                health
+            })
+            .collect()
+    }
+}
+
+// Relay urls come back from the pool with a trailing slash, config ones usually without
+fn normalize(url: &str) -> String {
+    url.trim_end_matches('/').to_string()
+}
@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::Path;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::nip18;
+use crate::models::post::Post;
+use crate::error::NostratuiError;
+
+/// One line of the log.
+///
+/// Posts cached before signed events were kept can't be turned back into
+/// events, so they stay as `Legacy` until the original event is refetched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Record {
+    Event(Event),
+    Legacy(LegacyPost),
+}
+
+/// The shape posts were cached in before signed events were kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyPost {
+    pub user: String,
+    #[serde(default)]
+    pub pubkey: String,
+    pub timestamp: u64,
+    pub datetime: String,
+    pub content: String,
+    pub id: String,
+    pub root_id: Option<String>,
+    pub reply_id: Option<String>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    #[serde(default)]
+    pub participants: Vec<String>,
+}
+
+impl From<LegacyPost> for Post {
+    fn from(legacy: LegacyPost) -> Self {
+        // Without a key, keep the name the post was cached under so it can still be shown
+        let pubkey = if legacy.pubkey.is_empty() { legacy.user } else { legacy.pubkey };
+        Post {
+            pubkey,
+            timestamp: legacy.timestamp,
+            datetime: legacy.datetime,
+            content: legacy.content,
+            id: legacy.id,
+            root_id: legacy.root_id,
+            reply_id: legacy.reply_id,
+            mentions: legacy.mentions,
+            participants: legacy.participants,
+            reposted_id: None,
+            repost: None,
+        }
+    }
+}
+
+impl Record {
+    fn id(&self) -> String {
+        match self {
+            Record::Event(event) => event.id.to_hex(),
+            Record::Legacy(post) => post.id.clone(),
+        }
+    }
+
+    fn timestamp(&self) -> u64 {
+        match self {
+            Record::Event(event) => event.created_at.as_u64(),
+            Record::Legacy(post) => post.timestamp,
+        }
+    }
+
+    fn author(&self) -> String {
+        match self {
+            Record::Event(event) => event.pubkey.to_hex(),
+            Record::Legacy(post) if post.pubkey.is_empty() => post.user.clone(),
+            Record::Legacy(post) => post.pubkey.clone(),
+        }
+    }
+
+    /// Derive the post shown in the feed
+    pub fn to_post(&self) -> Post {
+        match self {
+            Record::Event(event) => Post::from_event(event),
+            Record::Legacy(post) => post.clone().into(),
+        }
+    }
+}
+
+/// Location of one JSON line in the log
+#[derive(Debug, Clone, Copy)]
+struct RecordPos {
+    offset: u64,
+    len: usize,
+    legacy: bool,
+}
+
+/// Append-only JSON lines log of signed events with in-memory indexes.
+///
+/// Lookups by id are a `BTreeMap` search followed by a single seek, and the
+/// timestamp and author indexes are ordered sets so ranges are cheap too.
+pub struct PostStore {
+    file: File,
+    end: u64,
+    by_id: BTreeMap<String, RecordPos>,
+    by_time: BTreeSet<(u64, String)>,
+    by_author: HashMap<String, BTreeSet<(u64, String)>>,
+}
+
+impl PostStore {
+    pub fn open(path: &Path) -> Result<Self, NostratuiError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to open post store: {}", e)))?;
+
+        let mut store = Self {
+            file,
+            end: 0,
+            by_id: BTreeMap::new(),
+            by_time: BTreeSet::new(),
+            by_author: HashMap::new(),
+        };
+        store.rebuild_index()?;
+        Ok(store)
+    }
+
+    fn rebuild_index(&mut self) -> Result<(), NostratuiError> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(&self.file);
+        let mut offset = 0u64;
+        let mut line = String::new();
+        let mut records = Vec::new();
+
+        loop {
+            line.clear();
+            let len = reader.read_line(&mut line)?;
+            if len == 0 {
+                break;
+            }
+            // Only the last line can lack its newline, left over from an interrupted write.
+            // Appends must start on a fresh line, so it is either finished or cut off.
+            if !line.ends_with('\n') {
+                match serde_json::from_str::<Record>(&line) {
+                    Ok(record) => {
+                        self.file.write_all(b"\n")?;
+                        let legacy = matches!(record, Record::Legacy(_));
+                        records.push((record, RecordPos { offset, len, legacy }));
+                        offset += len as u64 + 1;
+                    },
+                    Err(e) => {
+                        log::warn!("Dropping partial post store record at byte {}: {}", offset, e);
+                        self.file.set_len(offset)?;
+                    },
+                }
+                break;
+            }
+            // Signatures were checked when the events were inserted
+            match serde_json::from_str::<Record>(line.trim_end()) {
+                Ok(record) => {
+                    let legacy = matches!(record, Record::Legacy(_));
+                    records.push((record, RecordPos { offset, len, legacy }));
+                },
+                Err(e) => log::warn!("Skipping unreadable post store record at byte {}: {}", offset, e),
+            }
+            offset += len as u64;
+        }
+
+        self.end = offset;
+        for (record, pos) in records {
+            self.index(&record, pos);
+        }
+        Ok(())
+    }
+
+    fn index(&mut self, record: &Record, pos: RecordPos) {
+        let id = record.id();
+        let key = (record.timestamp(), id.clone());
+        // A refetched event supersedes the legacy record with the same id
+        if self.by_id.insert(id, pos).is_some() {
+            for index in self.by_author.values_mut() {
+                index.remove(&key);
+            }
+        }
+        self.by_time.insert(key.clone());
+        self.by_author
+            .entry(record.author())
+            .or_default()
+            .insert(key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// Whether a signed event is stored for `id`, legacy records don't count
+    pub fn contains_event(&self, id: &str) -> bool {
+        self.by_id.get(id).is_some_and(|pos| !pos.legacy)
+    }
+
+    /// Ids of records that still lack their signed event
+    pub fn legacy_ids(&self) -> Vec<String> {
+        self.by_id.iter()
+            .filter(|(_, pos)| pos.legacy)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Append events that aren't stored yet in a single write, returning how many were new.
+    ///
+    /// Events with an invalid signature, and reposts embedding such a note, are dropped here,
+    /// so the log only ever holds verified ones.
+    pub fn insert_events(&mut self, events: Vec<Event>) -> Result<usize, NostratuiError> {
+        let records = events.into_iter()
+            .filter(|event| !self.contains_event(&event.id.to_hex()))
+            .filter(|event| {
+                let valid = event.verify().is_ok() && nip18::has_valid_embed(event);
+                if !valid {
+                    log::warn!("Not storing event {} with an invalid signature or embedded note", event.id);
+                }
+                valid
+            })
+            .map(Record::Event)
+            .collect();
+        self.append(records)
+    }
+
+    /// Import posts that predate event storage, skipping ids already present
+    pub fn insert_legacy(&mut self, posts: Vec<LegacyPost>) -> Result<usize, NostratuiError> {
+        let posts = posts.into_iter()
+            .filter(|post| !self.by_id.contains_key(&post.id))
+            .map(Record::Legacy)
+            .collect();
+        self.append(posts)
+    }
+
+    fn append(&mut self, records: Vec<Record>) -> Result<usize, NostratuiError> {
+        let mut buffer = Vec::new();
+        let mut added = Vec::new();
+        let mut seen = HashSet::new();
+
+        for record in records {
+            let id = record.id();
+            if self.contains_event(&id) || !seen.insert(id) {
+                continue;
+            }
+            let mut line = serde_json::to_vec(&record)
+                .map_err(|e| NostratuiError::Cache(format!("Failed to serialize record: {}", e)))?;
+            line.push(b'\n');
+            let pos = RecordPos {
+                offset: self.end + buffer.len() as u64,
+                len: line.len(),
+                legacy: matches!(record, Record::Legacy(_)),
+            };
+            buffer.extend_from_slice(&line);
+            added.push((record, pos));
+        }
+
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        self.file.write_all(&buffer)
+            .and_then(|_| self.file.flush())
+            .map_err(|e| NostratuiError::Cache(format!("Failed to write post store: {}", e)))?;
+        self.end += buffer.len() as u64;
+
+        let count = added.len();
+        for (record, pos) in added {
+            self.index(&record, pos);
+        }
+        Ok(count)
+    }
+
+    pub fn get(&mut self, id: &str) -> Result<Option<Record>, NostratuiError> {
+        match self.by_id.get(id).copied() {
+            Some(pos) => self.read_at(pos).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_at(&mut self, pos: RecordPos) -> Result<Record, NostratuiError> {
+        let mut line = vec![0u8; pos.len];
+        self.file.seek(SeekFrom::Start(pos.offset))?;
+        self.file.read_exact(&mut line)?;
+        Ok(serde_json::from_slice(&line)?)
+    }
+
+    /// Every stored record, newest first
+    pub fn all(&mut self) -> Result<Vec<Record>, NostratuiError> {
+        self.range(None, None)
+    }
+
+    /// Records with `since <= timestamp <= until`, newest first
+    pub fn range(&mut self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Record>, NostratuiError> {
+        let keys = time_range(&self.by_time, since, until);
+        self.read_keys(keys)
+    }
+
+    /// Records by one author with `since <= timestamp <= until`, newest first
+    pub fn range_by_author(&mut self, author: &str, since: Option<u64>, until: Option<u64>) -> Result<Vec<Record>, NostratuiError> {
+        let keys = match self.by_author.get(author) {
+            Some(index) => time_range(index, since, until),
+            None => Vec::new(),
+        };
+        self.read_keys(keys)
+    }
+
+    fn read_keys(&mut self, ids: Vec<String>) -> Result<Vec<Record>, NostratuiError> {
+        let mut records = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(record) = self.get(&id)? {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+fn time_range(index: &BTreeSet<(u64, String)>, since: Option<u64>, until: Option<u64>) -> Vec<String> {
+    let lower = match since {
+        Some(since) => Bound::Included((since, String::new())),
+        None => Bound::Unbounded,
+    };
+    let upper = match until {
+        Some(until) if until < u64::MAX => Bound::Excluded((until + 1, String::new())),
+        _ => Bound::Unbounded,
+    };
+    index.range((lower, upper))
+        .rev()
+        .map(|(_, id)| id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_support::{forged, note, repost};
+
+    fn store_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("nostratui-store-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn partial_trailing_line_is_dropped_before_appending() {
+        let path = store_path("partial");
+        let keys = Keys::generate();
+        let first = note(&keys, "first");
+        let second = note(&keys, "second");
+
+        let mut contents = serde_json::to_vec(&Record::Event(first.clone())).unwrap();
+        contents.push(b'\n');
+        contents.extend_from_slice(b"{\"id\":\"cut off");
+        std::fs::write(&path, contents).unwrap();
+
+        let mut store = PostStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.insert_events(vec![second.clone()]).unwrap(), 1);
+        drop(store);
+
+        let mut store = PostStore::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert!(store.contains_event(&first.id.to_hex()));
+        assert!(store.contains_event(&second.id.to_hex()));
+        assert!(matches!(store.get(&second.id.to_hex()).unwrap(), Some(Record::Event(event)) if event.id == second.id));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reposts_embedding_a_forged_note_are_not_stored() {
+        let path = store_path("forged-embed");
+        let keys = Keys::generate();
+        let original = note(&keys, "original");
+        let e_tag = original.id.to_hex();
+
+        let mut store = PostStore::open(&path).unwrap();
+        let reposts = vec![repost(&forged(&original, "forged"), &e_tag, &keys), repost(&original.as_json(), &e_tag, &keys)];
+        assert_eq!(store.insert_events(reposts).unwrap(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn complete_trailing_line_without_newline_is_kept() {
+        let path = store_path("unterminated");
+        let keys = Keys::generate();
+        let first = note(&keys, "first");
+        let second = note(&keys, "second");
+
+        std::fs::write(&path, serde_json::to_vec(&Record::Event(first.clone())).unwrap()).unwrap();
+
+        let mut store = PostStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        store.insert_events(vec![second.clone()]).unwrap();
+        drop(store);
+
+        let mut store = PostStore::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert!(matches!(store.get(&first.id.to_hex()).unwrap(), Some(Record::Event(event)) if event.id == first.id));
+        assert!(matches!(store.get(&second.id.to_hex()).unwrap(), Some(Record::Event(event)) if event.id == second.id));
+        let _ = std::fs::remove_file(&path);
+    }
+}
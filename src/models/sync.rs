@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::cache::get_cache_dir;
+use crate::error::NostratuiError;
+
+/// Refetch a little before each cursor to cover relays that index late or clocks that drift
+const SYNC_OVERLAP_SECS: u64 = 5 * 60;
+
+/// A relay further behind than this is reported as having a gap
+pub const GAP_THRESHOLD_SECS: u64 = 60 * 60;
+
+/// How far one author has been synced from one relay
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SyncCursor {
+    /// Everything up to this time has been fetched from the relay, None until a request completed.
+    ///
+    /// Files written before this was optional may hold 0 for an author whose sync never completed,
+    /// which is read as None too.
+    #[serde(default, deserialize_with = "deserialize_synced_until")]
+    pub synced_until: Option<u64>,
+    /// `created_at` of the newest event the relay returned
+    pub newest_seen: Option<u64>,
+}
+
+fn deserialize_synced_until<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<u64>::deserialize(deserializer)?.filter(|until| *until > 0))
+}
+
+/// A relay that is further behind than the most up to date one
+#[derive(Debug, Clone)]
+pub struct SyncGap {
+    pub relay: String,
+    /// Oldest cursor among the requested authors on this relay
+    pub since: u64,
+    /// Seconds this relay lags the most up to date relay
+    pub behind_by: u64,
+}
+
+/// Sync cursors keyed by relay url, then hex author key
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    relays: HashMap<String, HashMap<String, SyncCursor>>,
+}
+
+pub fn get_sync_file() -> Result<PathBuf, NostratuiError> {
+    Ok(get_cache_dir()?.join("sync.json"))
+}
+
+impl SyncState {
+    pub fn load() -> Result<Self, NostratuiError> {
+        let sync_path = get_sync_file()?;
+        if !sync_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(sync_path)?)?)
+    }
+
+    pub fn save(&self) -> Result<(), NostratuiError> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to serialize sync state: {}", e)))?;
+        fs::write(get_sync_file()?, json)
+            .map_err(|e| NostratuiError::Cache(format!("Failed to write sync state: {}", e)))
+    }
+
+    pub fn cursor(&self, relay: &str, author: &PublicKey) -> Option<SyncCursor> {
+        self.relays.get(relay)?.get(&author.to_hex()).copied()
+    }
+
+    /// Oldest cursor among `authors` on `relay`, with `default` for authors never synced there
+    fn oldest_cursor(&self, relay: &str, authors: &[PublicKey], default: Timestamp) -> u64 {
+        authors.iter()
+            .map(|author| self.cursor(relay, author).and_then(|c| c.synced_until).unwrap_or(default.as_u64()))
+            .min()
+            .unwrap_or(default.as_u64())
+    }
+
+    /// Where a request for `authors` to `relay` should start so nothing since the last sync is missed
+    pub fn since_for(&self, relay: &str, authors: &[PublicKey], default: Timestamp) -> Timestamp {
+        let oldest = self.oldest_cursor(relay, authors, default);
+        Timestamp::from_secs(oldest.saturating_sub(SYNC_OVERLAP_SECS))
+    }
+
+    /// Mark `authors` as synced from `relay` up to `until`, which should be when the request started.
+    ///
+    /// Without `until` the relay may hold events the request didn't get, so only what was seen is noted.
+    pub fn record(&mut self, relay: &str, authors: &[PublicKey], until: Option<Timestamp>, events: &[Event]) {
+        let cursors = self.relays.entry(relay.to_string()).or_default();
+        for author in authors {
+            let cursor = cursors.entry(author.to_hex()).or_default();
+            if let Some(until) = until {
+                cursor.synced_until = cursor.synced_until.max(Some(until.as_u64()));
+            }
+            let newest = events.iter()
+                .filter(|event| event.pubkey == *author)
+                .map(|event| event.created_at.as_u64())
+                .max();
+            cursor.newest_seen = cursor.newest_seen.max(newest);
+        }
+    }
+
+    /// Relays lagging the most up to date relay by more than `threshold` seconds for `authors`
+    pub fn gaps(&self, relays: &[String], authors: &[PublicKey], default: Timestamp, threshold: u64) -> Vec<SyncGap> {
+        let cursors: Vec<(&String, u64)> = relays.iter()
+            .map(|relay| (relay, self.oldest_cursor(relay, authors, default)))
+            .collect();
+        let Some(best) = cursors.iter().map(|(_, since)| *since).max() else {
+            return Vec::new();
+        };
+
+        cursors.into_iter()
+            .filter(|(_, since)| best - since > threshold)
+            .map(|(relay, since)| SyncGap {
+                relay: relay.clone(),
+                since,
+                behind_by: best - since,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELAY: &str = "wss://relay.example";
+
+    #[test]
+    fn incomplete_sync_keeps_the_default_start() {
+        let author = Keys::generate().public_key();
+        let default = Timestamp::from_secs(1_000_000);
+        let mut sync = SyncState::default();
+
+        sync.record(RELAY, &[author], None, &[]);
+        assert_eq!(sync.cursor(RELAY, &author).unwrap().synced_until, None);
+        assert_eq!(sync.since_for(RELAY, &[author], default).as_u64(), 1_000_000 - SYNC_OVERLAP_SECS);
+        assert!(sync.gaps(&[RELAY.to_string()], &[author], default, GAP_THRESHOLD_SECS).is_empty());
+
+        sync.record(RELAY, &[author], Some(Timestamp::from_secs(2_000_000)), &[]);
+        assert_eq!(sync.since_for(RELAY, &[author], default).as_u64(), 2_000_000 - SYNC_OVERLAP_SECS);
+    }
+
+    #[test]
+    fn zero_cursors_from_older_files_are_unsynced() {
+        let author = Keys::generate().public_key();
+        let json = format!(r#"{{"relays":{{"{}":{{"{}":{{"synced_until":0,"newest_seen":5}}}}}}}}"#, RELAY, author.to_hex());
+        let sync: SyncState = serde_json::from_str(&json).unwrap();
+        let cursor = sync.cursor(RELAY, &author).unwrap();
+        assert_eq!((cursor.synced_until, cursor.newest_seen), (None, Some(5)));
+        assert_eq!(sync.since_for(RELAY, &[author], Timestamp::from_secs(10_000)).as_u64(), 10_000 - SYNC_OVERLAP_SECS);
+    }
+}
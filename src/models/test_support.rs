@@ -0,0 +1,34 @@
+//! Event-building helpers shared by the model tests
+
+use nostr_sdk::prelude::*;
+
+/// A well-formed id for notes the tests only refer to
+pub const NOTE_ID: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+pub fn note_id() -> EventId {
+    EventId::from_hex(NOTE_ID).unwrap()
+}
+
+pub fn tag(values: &[&str]) -> Tag {
+    Tag::parse(values.iter().copied()).unwrap()
+}
+
+pub fn sign(kind: Kind, content: &str, tags: Vec<Tag>, keys: &Keys) -> Event {
+    EventBuilder::new(kind, content).tags(tags).sign_with_keys(keys).unwrap()
+}
+
+pub fn note(keys: &Keys, content: &str) -> Event {
+    sign(Kind::TextNote, content, Vec::new(), keys)
+}
+
+/// A repost embedding `content`, usually the original's JSON, under an e tag for `e_tag`
+pub fn repost(content: &str, e_tag: &str, keys: &Keys) -> Event {
+    sign(Kind::Repost, content, vec![tag(&["e", e_tag])], keys)
+}
+
+/// The event's JSON with the content swapped, so the id and signature no longer match
+pub fn forged(event: &Event, content: &str) -> String {
+    let mut json: serde_json::Value = serde_json::from_str(&event.as_json()).unwrap();
+    json["content"] = serde_json::Value::from(content);
+    json.to_string()
+}
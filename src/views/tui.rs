@@ -1,7 +1,9 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io;
+use std::time::{Duration, Instant};
 use ratatui::{
-    widgets::{Block, Borders, List, ListItem, Paragraph, ListState},
-    layout::{Layout, Constraint, Direction},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, ListState},
+    layout::{Layout, Constraint, Direction, Rect},
     style::{Style, Color, Modifier},
     text::{Line, Span},
     Frame,
@@ -13,9 +15,19 @@ use crossterm::{
     execute,
     event::{DisableMouseCapture, EnableMouseCapture},
 };
-use nostr_sdk::Timestamp;
-use crate::models::Post;
+use nostr_sdk::prelude::{FromBech32, Nip19Profile, PublicKey, Timestamp};
+use chrono::TimeZone;
+use crate::models::{Draft, Notification, NotificationKind, NotificationStore, Post, ProfileStore, ReactionCounts, ReactionStore, RelayHealth, ReplyTo};
+use crate::models::draft;
+use crate::models::reaction::REACTION_CHOICES;
+use crate::models::outbox::{OutboxEntry, OutboxState};
 use crate::views::widgets::StatefulList;
+use crate::error::NostratuiError;
+
+/// How long a toast stays in the status bar
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+/// Messages kept in the log, oldest are dropped first
+const MESSAGE_LOG_LIMIT: usize = 200;
 
 pub fn setup_terminal() -> io::Result<Terminal<ratatui::backend::CrosstermBackend<io::Stdout>>> {
     enable_raw_mode()?;
@@ -48,15 +60,17 @@ where
 
 pub fn render_ui<B: ratatui::backend::Backend>(
     f: &mut Frame<B>,
+    area: Rect,
     stateful_list: &mut StatefulList<Post>,
-    status: String,
+    profiles: &ProfileStore,
+    reactions: &ReactionStore,
 ) {
         // Create the layout
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([Constraint::Percentage(100)].as_ref())
-        .split(f.size());
+        .split(area);
 
     // Calculate the available width for text wrapping
     let available_width = chunks[0].width.saturating_sub(4); // Subtract border width and some padding
@@ -65,10 +79,14 @@ pub fn render_ui<B: ratatui::backend::Backend>(
     let items: Vec<ListItem> = stateful_list.items
         .iter()
         .map(|post| {
+            if post.is_repost() {
+                return ListItem::new(repost_lines(post, profiles, reactions, available_width as usize));
+            }
+
             // Create the header line with username and timestamp
             let mut header_parts = vec![
                 Span::styled(
-                    format!("{} - {} posted:", post.datetime, post.user),
+                    format!("{} - {} posted:", post.datetime, profiles.display_name(&post.pubkey)),
                     Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
                 )
             ];
@@ -88,10 +106,14 @@ pub fn render_ui<B: ratatui::backend::Backend>(
                 );
             }
 
+            if let Some(counts) = reactions.get(&post.id).filter(|counts| !counts.is_empty()) {
+                header_parts.push(reaction_span(counts));
+            }
+
             let header = Line::from(header_parts);
             
             // Create wrapped content by manually splitting the text
-            let content_lines = wrap_text(&post.content, available_width as usize);
+            let content_lines = wrap_text(&resolve_mentions(&post.content, profiles), available_width as usize);
             
             // Combine them into a multi-line item with spacing
             let mut all_lines = vec![
@@ -104,8 +126,14 @@ pub fn render_ui<B: ratatui::backend::Backend>(
                 all_lines.push(Line::from(line));
             }
 
-            // Add thread information footer if there are mentions
-            if !post.mentions.is_empty() {
+            // Add a footer naming the people and notes this post mentions
+            if !post.participants.is_empty() || !post.mentions.is_empty() {
+                let mut mentioned: Vec<String> = post.participants.iter()
+                    .map(|pubkey| format!("@{}", profiles.display_name(pubkey)))
+                    .collect();
+                if !post.mentions.is_empty() {
+                    mentioned.push(format!("{} note(s)", post.mentions.len()));
+                }
                 all_lines.push(Line::from(""));
                 all_lines.push(Line::from(vec![
                     Span::styled(
@@ -113,7 +141,7 @@ pub fn render_ui<B: ratatui::backend::Backend>(
                         Style::default().fg(Color::Gray)
                     ),
                     Span::styled(
-                        post.mentions.join(", "),
+                        mentioned.join(", "),
                         Style::default().fg(Color::Gray)
                     )
                 ]));
@@ -128,7 +156,7 @@ pub fn render_ui<B: ratatui::backend::Backend>(
 
     // Create a List from the items and highlight the currently selected one
     let list = List::new(items)
-        .block(Block::default().title(status).borders(Borders::ALL))
+        .block(Block::default().title("Feed").borders(Borders::ALL))
         .highlight_style(
             Style::default()
                 .bg(Color::Gray)
@@ -140,8 +168,74 @@ pub fn render_ui<B: ratatui::backend::Backend>(
     f.render_stateful_widget(list, chunks[0], &mut stateful_list.state);
 }
 
+// A repost with the reposted note inline below it, reactions counted on the original
+fn repost_lines(post: &Post, profiles: &ProfileStore, reactions: &ReactionStore, width: usize) -> Vec<Line<'static>> {
+    let mut header = vec![Span::styled(
+        format!("{} - {} reposted", post.datetime, profiles.display_name(&post.pubkey)),
+        Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+    )];
+    if let Some(counts) = reactions.get(&post.target().id).filter(|counts| !counts.is_empty()) {
+        header.push(reaction_span(counts));
+    }
+    let mut lines = vec![Line::from(header), Line::from("")];
+
+    let quote_style = Style::default().fg(Color::Gray);
+    match &post.repost {
+        Some(original) => {
+            lines.push(Line::from(Span::styled(
+                format!("│ {} - {}", profiles.display_name(&original.pubkey), original.datetime),
+                Style::default().fg(Color::Cyan),
+            )));
+            for line in wrap_text(&resolve_mentions(&original.content, profiles), width.saturating_sub(2).max(20)) {
+                lines.push(Line::from(vec![Span::styled("│ ", quote_style), Span::raw(line)]));
+            }
+        },
+        None => lines.push(Line::from(Span::styled("│ Loading reposted note...", quote_style))),
+    }
+    lines.push(Line::from(""));
+    lines
+}
+
+// Reaction counts for a post header, in green once we've reacted ourselves
+fn reaction_span(counts: &ReactionCounts) -> Span<'static> {
+    let color = if counts.mine.is_some() { Color::Green } else { Color::Magenta };
+    Span::styled(format!("  {}", counts.summary()), Style::default().fg(color))
+}
+
+// Replace `nostr:npub1…` and `nostr:nprofile1…` references with `@name`
+fn resolve_mentions(text: &str, profiles: &ProfileStore) -> String {
+    let mut resolved = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("nostr:") {
+        resolved.push_str(&rest[..start]);
+        let uri = &rest[start..];
+        let end = uri["nostr:".len()..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .map_or(uri.len(), |i| i + "nostr:".len());
+        let bech32 = &uri["nostr:".len()..end];
+
+        let public_key = PublicKey::from_bech32(bech32)
+            .ok()
+            .or_else(|| Nip19Profile::from_bech32(bech32).ok().map(|profile| profile.public_key));
+        match public_key {
+            Some(public_key) => {
+                resolved.push('@');
+                resolved.push_str(&profiles.display_name_for(&public_key));
+            },
+            None => resolved.push_str(&uri[..end]),
+        }
+        rest = &uri[end..];
+    }
+
+    resolved.push_str(rest);
+    resolved
+}
+
 // Helper function to manually wrap text to a specified width
 fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    // A zero width would never advance, narrow terminals get one character per line instead
+    let width = width.max(1);
     let mut wrapped_lines = Vec::new();
     
     for line in text.lines() {
@@ -193,18 +287,49 @@ fn wrap_text(text: &str, width: usize) -> Vec<String> {
 }
 
 pub struct ThreadView {
-    pub posts: Vec<Post>,
+    /// Posts in tree order, each with its depth below the root
+    pub posts: Vec<(usize, Post)>,
+    /// The post the thread was opened from
+    pub focus: String,
     pub state: ListState,
+    /// Whether more of the thread is still being fetched
+    pub loading: bool,
+    /// Why loading the thread stopped early, if it did
+    pub error: Option<String>,
 }
 
 impl ThreadView {
-    pub fn new(posts: Vec<Post>) -> Self {
+    /// Arrange `posts` as a reply tree and select `focus` within it.
+    pub fn new(posts: Vec<Post>, focus: &str) -> Self {
+        let posts = arrange_thread(posts);
         let mut state = ListState::default();
-        state.select(Some(0));
-        Self { posts, state }
+        let selected = posts.iter().position(|(_, post)| post.id == focus);
+        state.select(selected.or(if posts.is_empty() { None } else { Some(0) }));
+        Self { posts, focus: focus.to_string(), state, loading: false, error: None }
+    }
+
+    /// Merge newly fetched posts into the tree, keeping the same post selected
+    pub fn add_posts(&mut self, new_posts: Vec<Post>) {
+        let selected_id = self.selected().map(|post| post.id.clone());
+        let mut posts: Vec<Post> = self.posts.drain(..).map(|(_, post)| post).collect();
+        for post in new_posts {
+            if !posts.iter().any(|existing| existing.id == post.id) {
+                posts.push(post);
+            }
+        }
+        self.posts = arrange_thread(posts);
+        let selected = selected_id.and_then(|id| self.posts.iter().position(|(_, post)| post.id == id));
+        self.state.select(selected.or(if self.posts.is_empty() { None } else { Some(0) }));
+    }
+
+    pub fn selected(&self) -> Option<&Post> {
+        self.state.selected().and_then(|i| self.posts.get(i)).map(|(_, post)| post)
     }
 
     pub fn next(&mut self) {
+        if self.posts.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.posts.len() - 1 {
@@ -219,6 +344,9 @@ impl ThreadView {
     }
 
     pub fn previous(&mut self) {
+        if self.posts.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -233,7 +361,56 @@ impl ThreadView {
     }
 }
 
-pub fn render_thread_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, thread_view: &ThreadView) {
+/// Order `posts` depth first as a reply tree, pairing each with its depth.
+///
+/// A post hangs under its `reply_id` parent, falling back to its root when the
+/// parent wasn't found; posts with neither in the thread start a top-level branch.
+/// Posts in a parent cycle can't be reached from any branch, so the oldest post
+/// of each cycle starts one of its own.
+fn arrange_thread(posts: Vec<Post>) -> Vec<(usize, Post)> {
+    let ids: HashSet<&str> = posts.iter().map(|post| post.id.as_str()).collect();
+    let parent_of = |post: &Post| {
+        [&post.reply_id, &post.root_id].into_iter()
+            .flatten()
+            .find(|id| *id != &post.id && ids.contains(id.as_str()))
+            .cloned()
+    };
+
+    let mut children: HashMap<Option<String>, Vec<&Post>> = HashMap::new();
+    for post in posts.iter() {
+        children.entry(parent_of(post)).or_default().push(post);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by_key(|post| post.timestamp);
+    }
+
+    let mut by_age: Vec<&Post> = posts.iter().collect();
+    by_age.sort_by_key(|post| post.timestamp);
+    let mut leftovers = by_age.into_iter();
+
+    let mut ordered = Vec::with_capacity(posts.len());
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut roots: Vec<&Post> = children.get(&None).cloned().unwrap_or_default();
+    loop {
+        let mut stack: Vec<(usize, &Post)> = roots.iter().rev().map(|post| (0, *post)).collect();
+        while let Some((depth, post)) = stack.pop() {
+            if !visited.insert(post.id.as_str()) {
+                continue;
+            }
+            if let Some(replies) = children.get(&Some(post.id.clone())) {
+                stack.extend(replies.iter().rev().map(|reply| (depth + 1, *reply)));
+            }
+            ordered.push((depth, post.clone()));
+        }
+        match leftovers.find(|post| !visited.contains(post.id.as_str())) {
+            Some(post) => roots = vec![post],
+            None => break,
+        }
+    }
+    ordered
+}
+
+pub fn render_thread_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, thread_view: &ThreadView, profiles: &ProfileStore, reactions: &ReactionStore) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
@@ -241,25 +418,47 @@ pub fn render_thread_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, thread
             Constraint::Length(3),
             Constraint::Min(0),
         ])
-        .split(f.size());
+        .split(area);
 
-    // Title
-    let title = Paragraph::new("Thread View (q to return)")
-        .style(Style::default().add_modifier(Modifier::BOLD))
+    // Title, with the state of the fetch while it's running or after it failed
+    let (status, style) = match (&thread_view.error, thread_view.loading) {
+        (Some(error), _) => (format!("Thread View - {} (q to return)", error), Style::default().fg(Color::Red)),
+        (None, true) => (String::from("Thread View - Loading... (Esc to stop, q to return)"), Style::default().fg(Color::Yellow)),
+        (None, false) => (String::from("Thread View (q to return)"), Style::default()),
+    };
+    let title = Paragraph::new(status)
+        .style(style.add_modifier(Modifier::BOLD))
         .alignment(Alignment::Center);
     f.render_widget(title, chunks[0]);
 
-    // Thread posts
+    // Thread posts, indented by their depth in the reply tree
+    let width = chunks[1].width.saturating_sub(4) as usize;
     let items: Vec<ListItem> = thread_view.posts
         .iter()
-        .map(|post| {
-            let content = format!(
-                "{} - {}\n{}",
-                post.user,
-                post.datetime,
-                post.content
-            );
-            ListItem::new(content)
+        .map(|(depth, post)| {
+            let indent = "  ".repeat(*depth);
+            let marker = if *depth > 0 { "└ " } else { "" };
+            let mut header = vec![Span::styled(
+                format!("{}{}{} - {}", indent, marker, profiles.display_name(&post.pubkey), post.datetime),
+                Style::default().fg(Color::Cyan),
+            )];
+            if let Some(counts) = reactions.get(&post.id).filter(|counts| !counts.is_empty()) {
+                header.push(reaction_span(counts));
+            }
+            let mut lines = vec![Line::from(header)];
+            let body_indent = format!("{}{}", indent, " ".repeat(marker.chars().count()));
+            let body_width = width.saturating_sub(body_indent.len()).max(20);
+            for line in wrap_text(&resolve_mentions(&post.content, profiles), body_width) {
+                lines.push(Line::from(format!("{}{}", body_indent, line)));
+            }
+            lines.push(Line::from(""));
+
+            let item = ListItem::new(lines);
+            if post.id == thread_view.focus {
+                item.style(Style::default().fg(Color::Yellow))
+            } else {
+                item
+            }
         })
         .collect();
 
@@ -270,3 +469,902 @@ pub fn render_thread_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, thread
 
     f.render_stateful_widget(list, chunks[1], &mut thread_view.state.clone());
 }
+
+pub struct OutboxView {
+    pub entries: Vec<OutboxEntry>,
+    pub state: ListState,
+}
+
+impl OutboxView {
+    pub fn new(entries: Vec<OutboxEntry>) -> Self {
+        let mut view = Self { entries, state: ListState::default() };
+        view.clamp_selection();
+        view
+    }
+
+    /// Swap in a fresh copy of the outbox, keeping the selection in range
+    pub fn set_entries(&mut self, entries: Vec<OutboxEntry>) {
+        self.entries = entries;
+        self.clamp_selection();
+    }
+
+    pub fn selected(&self) -> Option<&OutboxEntry> {
+        self.state.selected().and_then(|i| self.entries.get(i))
+    }
+
+    pub fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.entries.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        if self.entries.is_empty() {
+            self.state.select(None);
+        } else {
+            let i = self.state.selected().unwrap_or(0).min(self.entries.len() - 1);
+            self.state.select(Some(i));
+        }
+    }
+}
+
+pub fn render_outbox_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, outbox_view: &mut OutboxView) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let title = Paragraph::new("Outbox (r retry, d discard, q to return)")
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    let now = Timestamp::now().as_u64();
+    let available_width = chunks[1].width.saturating_sub(4) as usize;
+    let items: Vec<ListItem> = outbox_view.entries
+        .iter()
+        .map(|entry| {
+            let status = match entry.state {
+                OutboxState::Pending if entry.next_retry > now => Span::styled(
+                    format!("pending, retry in {}s", entry.next_retry - now),
+                    Style::default().fg(Color::Yellow)
+                ),
+                OutboxState::Pending => Span::styled("pending, retrying", Style::default().fg(Color::Yellow)),
+                OutboxState::Failed => Span::styled("failed", Style::default().fg(Color::Red)),
+            };
+            let mut lines = vec![
+                Line::from(vec![
+                    Span::styled(
+                        format!("{} attempt(s) - ", entry.attempts),
+                        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+                    ),
+                    status,
+                ]),
+            ];
+            for line in wrap_text(&entry.event.content, available_width) {
+                lines.push(Line::from(line));
+            }
+            if let Some(error) = &entry.last_error {
+                lines.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Gray))));
+            }
+            lines.push(Line::from(""));
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().title("Unsent notes").borders(Borders::ALL))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Gray)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD)
+        );
+
+    f.render_stateful_widget(list, chunks[1], &mut outbox_view.state);
+}
+
+pub struct DraftsView {
+    pub drafts: Vec<Draft>,
+    pub state: ListState,
+}
+
+impl DraftsView {
+    pub fn new(drafts: Vec<Draft>) -> Self {
+        let mut view = Self { drafts, state: ListState::default() };
+        view.clamp_selection();
+        view
+    }
+
+    /// Swap in a fresh copy of the drafts, keeping the selection in range
+    pub fn set_drafts(&mut self, drafts: Vec<Draft>) {
+        self.drafts = drafts;
+        self.clamp_selection();
+    }
+
+    pub fn selected(&self) -> Option<&Draft> {
+        self.state.selected().and_then(|i| self.drafts.get(i))
+    }
+
+    pub fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.drafts.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        if self.drafts.is_empty() {
+            self.state.select(None);
+        } else {
+            let i = self.state.selected().unwrap_or(0).min(self.drafts.len() - 1);
+            self.state.select(Some(i));
+        }
+    }
+}
+
+pub fn render_drafts_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, drafts_view: &mut DraftsView, profiles: &ProfileStore) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let title = Paragraph::new("Drafts (Enter resume, p publish, d delete, q to return)")
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    let available_width = chunks[1].width.saturating_sub(4) as usize;
+    let items: Vec<ListItem> = drafts_view.drafts
+        .iter()
+        .map(|draft| {
+            let target = match &draft.reply_to {
+                Some(reply_to) => match &reply_to.reply.author {
+                    Some(author) => format!("Reply to {}", profiles.display_name(author)),
+                    None => format!("Reply to {}", draft.display_key()),
+                },
+                None => String::from("New note"),
+            };
+            let edited = chrono::Local.timestamp_opt(draft.updated_at as i64, 0)
+                .single()
+                .map_or_else(String::new, |time| time.format("%H:%M %h-%d-%Y").to_string());
+            let mut lines = vec![Line::from(Span::styled(
+                format!("{} - edited {}", target, edited),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ))];
+            for line in wrap_text(&draft.content, available_width).into_iter().take(3) {
+                lines.push(Line::from(line));
+            }
+            lines.push(Line::from(""));
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().title("Drafts").borders(Borders::ALL))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Gray)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD)
+        );
+
+    f.render_stateful_widget(list, chunks[1], &mut drafts_view.state);
+}
+
+/// A row of reactions to pick from for one post
+pub struct ReactionPicker {
+    pub post: Post,
+    pub selected: usize,
+}
+
+impl ReactionPicker {
+    pub fn new(post: Post) -> Self {
+        Self { post, selected: 0 }
+    }
+
+    pub fn choice(&self) -> &'static str {
+        REACTION_CHOICES[self.selected]
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % REACTION_CHOICES.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = (self.selected + REACTION_CHOICES.len() - 1) % REACTION_CHOICES.len();
+    }
+
+    /// Select by the number shown next to each choice, starting at 1
+    pub fn select_number(&mut self, number: usize) -> bool {
+        if (1..=REACTION_CHOICES.len()).contains(&number) {
+            self.selected = number - 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The picker as a small box over the middle of `area`
+pub fn render_reaction_picker<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, picker: &ReactionPicker, profiles: &ProfileStore) {
+    let width = 44.min(area.width);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(5) / 2,
+        width,
+        height: 5.min(area.height),
+    };
+
+    let choices: Vec<Span> = REACTION_CHOICES.iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let style = if i == picker.selected {
+                Style::default().bg(Color::Gray).fg(Color::Black).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Span::styled(format!(" {}{} ", i + 1, choice), style)
+        })
+        .collect();
+    let lines = vec![
+        Line::from(choices),
+        Line::from(""),
+        Line::from(Span::styled("Enter react, Esc cancel", Style::default().fg(Color::Gray))),
+    ];
+
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(Block::default().title(format!("React to {}", profiles.display_name(&picker.post.pubkey))).borders(Borders::ALL)),
+        popup,
+    );
+}
+
+/// Notifications, one kind at a time or all together
+pub struct NotificationsView {
+    /// None shows every kind
+    pub group: Option<NotificationKind>,
+    pub state: ListState,
+}
+
+impl NotificationsView {
+    pub fn new(store: &NotificationStore) -> Self {
+        let mut view = Self { group: None, state: ListState::default() };
+        view.clamp_selection(store);
+        view
+    }
+
+    pub fn selected<'a>(&self, store: &'a NotificationStore) -> Option<&'a Notification> {
+        self.state.selected().and_then(|i| store.list(self.group).get(i).copied())
+    }
+
+    pub fn next(&mut self, store: &NotificationStore) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(store.list(self.group).len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    /// Switch to the next kind, after the last one comes the list of all of them
+    pub fn next_group(&mut self, store: &NotificationStore) {
+        let position = self.group.and_then(|group| NotificationKind::ALL.iter().position(|kind| *kind == group));
+        self.group = match position {
+            None => Some(NotificationKind::ALL[0]),
+            Some(i) => NotificationKind::ALL.get(i + 1).copied(),
+        };
+        self.state.select(Some(0));
+        self.clamp_selection(store);
+    }
+
+    pub fn previous_group(&mut self, store: &NotificationStore) {
+        let position = self.group.and_then(|group| NotificationKind::ALL.iter().position(|kind| *kind == group));
+        self.group = match position {
+            None => NotificationKind::ALL.last().copied(),
+            Some(0) => None,
+            Some(i) => Some(NotificationKind::ALL[i - 1]),
+        };
+        self.state.select(Some(0));
+        self.clamp_selection(store);
+    }
+
+    /// Keep the selection in range as notifications arrive
+    pub fn clamp_selection(&mut self, store: &NotificationStore) {
+        let len = store.list(self.group).len();
+        if len == 0 {
+            self.state.select(None);
+        } else {
+            let i = self.state.selected().unwrap_or(0).min(len - 1);
+            self.state.select(Some(i));
+        }
+    }
+}
+
+pub fn render_notifications_view<B: ratatui::backend::Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    view: &mut NotificationsView,
+    store: &NotificationStore,
+    profiles: &ProfileStore,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    // One tab per kind with its unread count, the current one highlighted
+    let groups = std::iter::once((None, "All")).chain(NotificationKind::ALL.iter().map(|kind| (Some(*kind), kind.label())));
+    let mut tabs = Vec::new();
+    for (group, label) in groups {
+        let unread = store.unread(group);
+        let text = if unread > 0 { format!(" {} ({}) ", label, unread) } else { format!(" {} ", label) };
+        let style = if group == view.group {
+            Style::default().bg(Color::Gray).fg(Color::Black).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        tabs.push(Span::styled(text, style));
+    }
+    let title = Paragraph::new(vec![
+        Line::from(tabs),
+        Line::from(Span::styled("Tab next group, Enter open, a mark all read, q to return", Style::default().fg(Color::Gray))),
+    ]).alignment(Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    let available_width = chunks[1].width.saturating_sub(4) as usize;
+    let items: Vec<ListItem> = store.list(view.group)
+        .into_iter()
+        .map(|notification| {
+            let what = match notification.kind {
+                NotificationKind::Reply => "replied",
+                NotificationKind::Mention => "mentioned you",
+                NotificationKind::Reaction => "reacted",
+                NotificationKind::Repost => "reposted your note",
+                NotificationKind::Zap => "zapped",
+            };
+            let when = chrono::Local.timestamp_opt(notification.created_at as i64, 0)
+                .single()
+                .map_or_else(String::new, |time| time.format("%H:%M %h-%d-%Y").to_string());
+            let header_style = if notification.read {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            };
+            let marker = if notification.read { "  " } else { "• " };
+            let mut lines = vec![Line::from(Span::styled(
+                format!("{}{} {} - {}", marker, profiles.display_name(&notification.author), what, when),
+                header_style,
+            ))];
+            let content = match notification.kind {
+                NotificationKind::Reply | NotificationKind::Mention => resolve_mentions(&notification.content, profiles),
+                _ => notification.content.clone(),
+            };
+            for line in wrap_text(&content, available_width.saturating_sub(2)).into_iter().take(4) {
+                lines.push(Line::from(format!("  {}", line)));
+            }
+            lines.push(Line::from(""));
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().title("Notifications").borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    f.render_stateful_widget(list, chunks[1], &mut view.state);
+}
+
+pub struct RelayView {
+    pub relays: Vec<RelayHealth>,
+    pub state: ListState,
+}
+
+impl RelayView {
+    pub fn new(relays: Vec<RelayHealth>) -> Self {
+        let mut view = Self { relays: Vec::new(), state: ListState::default() };
+        view.set_relays(relays);
+        view
+    }
+
+    /// Swap in fresh relay health, keeping the selection in range
+    pub fn set_relays(&mut self, relays: Vec<RelayHealth>) {
+        self.relays = relays;
+        if self.relays.is_empty() {
+            self.state.select(None);
+        } else {
+            let selected = self.state.selected().unwrap_or(0).min(self.relays.len() - 1);
+            self.state.select(Some(selected));
+        }
+    }
+
+    pub fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.relays.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+}
+
+pub fn render_relay_view<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, relay_view: &mut RelayView) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let connected = relay_view.relays.iter().filter(|health| health.is_connected()).count();
+    let title = Paragraph::new(format!("Relays, {}/{} connected (q to return)", connected, relay_view.relays.len()))
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    let available_width = chunks[1].width.saturating_sub(4) as usize;
+    let items: Vec<ListItem> = relay_view.relays
+        .iter()
+        .map(|health| {
+            let state_color = if health.is_connected() { Color::Green } else { Color::Red };
+            let latency = health.latency_ms.map_or_else(|| String::from("-"), |ms| format!("{}ms", ms));
+            let mut lines = vec![
+                Line::from(vec![
+                    Span::styled(health.url.clone(), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+                    Span::styled(format!(" {}", health.state), Style::default().fg(state_color)),
+                    Span::raw(format!(" | latency {} | {} events", latency, health.events_received)),
+                ]),
+            ];
+            if let Some(error) = &health.last_error {
+                for line in wrap_text(&format!("last error: {}", error), available_width) {
+                    lines.push(Line::from(Span::styled(line, Style::default().fg(Color::Red))));
+                }
+            }
+            // The latest few NOTICE/CLOSED messages, newest last
+            for entry in health.messages.iter().rev().take(3).rev() {
+                for line in wrap_text(&format!("{} {}", entry.kind, entry.message), available_width) {
+                    lines.push(Line::from(Span::styled(line, Style::default().fg(Color::Gray))));
+                }
+            }
+            lines.push(Line::from(""));
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().title("Relay status").borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    f.render_stateful_widget(list, chunks[1], &mut relay_view.state);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComposerMode {
+    Editing,
+    /// Showing the note as it will look in the feed
+    Preview,
+    /// Waiting for a yes or no before sending
+    Confirm,
+}
+
+/// A note being written in the TUI, with the post it replies to kept in view
+pub struct Composer {
+    lines: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    pub parent: Option<Post>,
+    pub reply_to: Option<ReplyTo>,
+    /// Hex id of the quoted note, whose reference is part of the text
+    pub quote: Option<String>,
+    pub mode: ComposerMode,
+}
+
+impl Composer {
+    pub fn new(parent: Option<Post>, reply_to: Option<ReplyTo>) -> Self {
+        Self {
+            lines: vec![Vec::new()],
+            row: 0,
+            col: 0,
+            parent,
+            reply_to,
+            quote: None,
+            mode: ComposerMode::Editing,
+        }
+    }
+
+    /// A new note quoting `original`, with `reference` to it below where the cursor starts
+    pub fn quoting(original: Post, reference: &str) -> Self {
+        let quote = original.id.clone();
+        let mut composer = Self::new(Some(original), None);
+        composer.quote = Some(quote);
+        composer.set_text(&format!("\n\n{}", reference));
+        composer.row = 0;
+        composer.col = 0;
+        composer
+    }
+
+    /// Key of the draft this composer saves to and restores from
+    pub fn draft_key(&self) -> String {
+        draft::key_for(self.reply_to.as_ref(), self.quote.as_deref())
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Replace the text, e.g. with what came back from $EDITOR, and put the cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        self.lines = text.split('\n').map(|line| line.chars().collect()).collect();
+        self.row = self.lines.len() - 1;
+        self.col = self.lines[self.row].len();
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.lines.iter().map(Vec::len).sum::<usize>() + self.lines.len() - 1
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.lines.iter().all(|line| line.iter().all(|c| c.is_whitespace()))
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.lines[self.row].insert(self.col, c);
+        self.col += 1;
+    }
+
+    pub fn newline(&mut self) {
+        let rest = self.lines[self.row].split_off(self.col);
+        self.row += 1;
+        self.col = 0;
+        self.lines.insert(self.row, rest);
+    }
+
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            self.lines[self.row].remove(self.col);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.lines[self.row].len();
+            self.lines[self.row].extend(line);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.col < self.lines[self.row].len() {
+            self.lines[self.row].remove(self.col);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].extend(line);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.lines[self.row].len();
+        }
+    }
+
+    pub fn right(&mut self) {
+        if self.col < self.lines[self.row].len() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.lines[self.row].len());
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.lines[self.row].len());
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.col = self.lines[self.row].len();
+    }
+
+    /// The text broken into rows of `width` characters, and the row and column of the cursor.
+    ///
+    /// Lines are cut at the width rather than at words so the cursor maps straight onto them.
+    fn wrapped(&self, width: usize) -> (Vec<String>, (usize, usize)) {
+        let width = width.max(1);
+        let mut rows = Vec::new();
+        let mut cursor = (0, 0);
+        for (index, line) in self.lines.iter().enumerate() {
+            if index == self.row {
+                cursor = (rows.len() + self.col / width, self.col % width);
+            }
+            // One spare row when the line fills its last one, so the cursor has somewhere to go
+            for chunk in 0..=line.len() / width {
+                let end = ((chunk + 1) * width).min(line.len());
+                rows.push(line[chunk * width..end].iter().collect());
+            }
+        }
+        (rows, cursor)
+    }
+}
+
+pub fn render_composer<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, composer: &Composer, profiles: &ProfileStore) {
+    let title = match (&composer.parent, &composer.reply_to) {
+        (Some(parent), Some(_)) => format!("Reply to {}", profiles.display_name(&parent.pubkey)),
+        (Some(parent), None) => format!("Quote {}", profiles.display_name(&parent.pubkey)),
+        (None, _) => String::from("New note"),
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let width = inner.width.saturating_sub(2) as usize;
+
+    // The parent post, quoted and cut short so the note being written keeps most of the space
+    let quote: Vec<Line> = composer.parent.iter()
+        .flat_map(|parent| {
+            let header = format!("> {} - {}", profiles.display_name(&parent.pubkey), parent.datetime);
+            std::iter::once(header).chain(
+                wrap_text(&resolve_mentions(&parent.content, profiles), width.saturating_sub(2).max(20))
+                    .into_iter()
+                    .map(|line| format!("> {}", line))
+            )
+        })
+        .take(8)
+        .map(|line| Line::from(Span::styled(line, Style::default().fg(Color::DarkGray))))
+        .collect();
+    let quote_height = if quote.is_empty() { 0 } else { quote.len() as u16 + 1 };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(quote_height),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    f.render_widget(Paragraph::new(quote), chunks[0]);
+
+    let body = chunks[1];
+    let height = body.height as usize;
+    match composer.mode {
+        ComposerMode::Preview => {
+            let lines: Vec<Line> = wrap_text(&resolve_mentions(&composer.text(), profiles), width.max(20))
+                .into_iter()
+                .map(Line::from)
+                .collect();
+            f.render_widget(Paragraph::new(lines).style(Style::default().fg(Color::Cyan)), body);
+        },
+        ComposerMode::Editing | ComposerMode::Confirm => {
+            let (rows, (row, col)) = composer.wrapped(body.width as usize);
+            let scroll = (row + 1).saturating_sub(height);
+            let lines: Vec<Line> = rows.into_iter().map(Line::from).collect();
+            f.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), body);
+            if composer.mode == ComposerMode::Editing {
+                f.set_cursor(body.x + col as u16, body.y + (row - scroll) as u16);
+            }
+        },
+    }
+
+    let count = format!("{} chars", composer.char_count());
+    let (hint, style) = match composer.mode {
+        ComposerMode::Editing => (format!("{} | Ctrl-s send, Ctrl-p preview, Ctrl-e $EDITOR, Esc save draft", count), Style::default().fg(Color::Gray)),
+        ComposerMode::Preview => (format!("{} | Preview - Ctrl-p or Esc to edit, Ctrl-s send", count), Style::default().fg(Color::Gray)),
+        ComposerMode::Confirm => (format!("Send this {}? (y/n)", if composer.reply_to.is_some() { "reply" } else { "note" }), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+    };
+    f.render_widget(Paragraph::new(hint).style(style), chunks[2]);
+}
+
+/// Something for the user to see, sent to the TUI by controllers and background tasks
+pub enum UiMessage {
+    Info(String),
+    /// What was being done when it failed, and the error
+    Error(String, NostratuiError),
+}
+
+pub struct LogEntry {
+    pub time: String,
+    pub text: String,
+    pub is_error: bool,
+}
+
+/// Recent messages, newest first, with the latest one shown as a toast for a while
+#[derive(Default)]
+pub struct Messages {
+    entries: VecDeque<LogEntry>,
+    toast_until: Option<Instant>,
+}
+
+impl Messages {
+    pub fn push(&mut self, message: UiMessage) {
+        let (text, is_error) = match message {
+            UiMessage::Info(text) => (text, false),
+            UiMessage::Error(context, error) => (format!("{}: {}", context, error), true),
+        };
+        if is_error {
+            log::warn!("{}", text);
+        }
+        self.entries.push_front(LogEntry {
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+            text,
+            is_error,
+        });
+        self.entries.truncate(MESSAGE_LOG_LIMIT);
+        self.toast_until = Some(Instant::now() + TOAST_DURATION);
+    }
+
+    /// The latest message while its toast hasn't expired
+    pub fn toast(&self) -> Option<&LogEntry> {
+        self.toast_until
+            .filter(|until| Instant::now() < *until)
+            .and_then(|_| self.entries.front())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Split off the bottom line of the screen for the status bar
+pub fn split_status_bar(area: Rect) -> (Rect, Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(area);
+    (chunks[0], chunks[1])
+}
+
+/// One line with the current status, replaced by the latest message while it's fresh
+pub fn render_status_bar<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, status: &str, messages: &Messages) {
+    let line = match messages.toast() {
+        Some(entry) => {
+            let color = if entry.is_error { Color::Red } else { Color::Green };
+            Line::from(Span::styled(format!(" {}", entry.text), Style::default().fg(color).add_modifier(Modifier::BOLD)))
+        },
+        None => {
+            let mut spans = vec![Span::raw(format!(" {}", status))];
+            if !messages.is_empty() {
+                spans.push(Span::styled(
+                    format!(" | {} message(s) (m)", messages.len()),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            Line::from(spans)
+        },
+    };
+    f.render_widget(Paragraph::new(line), area);
+}
+
+pub struct MessageLogView {
+    pub state: ListState,
+}
+
+impl MessageLogView {
+    pub fn new(messages: &Messages) -> Self {
+        let mut state = ListState::default();
+        if !messages.is_empty() {
+            state.select(Some(0));
+        }
+        Self { state }
+    }
+
+    pub fn next(&mut self, messages: &Messages) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(messages.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+}
+
+pub fn render_message_log<B: ratatui::backend::Backend>(f: &mut Frame<B>, area: Rect, messages: &Messages, view: &mut MessageLogView) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let title = Paragraph::new("Messages (q to return)")
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    let available_width = chunks[1].width.saturating_sub(14) as usize;
+    let items: Vec<ListItem> = messages.entries
+        .iter()
+        .map(|entry| {
+            let style = if entry.is_error { Style::default().fg(Color::Red) } else { Style::default() };
+            let lines: Vec<Line> = wrap_text(&entry.text, available_width)
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| {
+                    let time = if i == 0 { entry.time.clone() } else { " ".repeat(entry.time.len()) };
+                    Line::from(vec![
+                        Span::styled(format!("{} ", time), Style::default().fg(Color::Gray)),
+                        Span::styled(text, style),
+                    ])
+                })
+                .collect();
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().title("Recent errors and events").borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    f.render_stateful_widget(list, chunks[1], &mut view.state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapping_to_zero_width_terminates() {
+        assert_eq!(wrap_text("ab c", 0), vec!["a", "b", "c"]);
+    }
+}
@@ -22,6 +22,31 @@ impl<T> StatefulList<T> {
         self.items.extend(new_items);
     }
 
+    /// Insert items into a list ordered by `f`, keeping the selection on the same item.
+    ///
+    /// Returns how many of the items landed above the selection.
+    pub fn insert_sorted_by_key<F, K>(&mut self, new_items: Vec<T>, mut f: F) -> usize
+        where
+            F: FnMut(&T) -> K,
+            K: Ord,
+    {
+        let mut inserted_above = 0;
+        for item in new_items {
+            let key = f(&item);
+            let position = self.items.partition_point(|existing| f(existing) <= key);
+            self.items.insert(position, item);
+            match self.state.selected() {
+                Some(selected) if position <= selected => {
+                    self.state.select(Some(selected + 1));
+                    inserted_above += 1;
+                },
+                Some(_) => {},
+                None => self.state.select(Some(0)),
+            }
+        }
+        inserted_above
+    }
+
     pub fn sort_by<F, K>(&mut self, f: F)
         where
             F: FnMut(&T) -> K,